@@ -9,7 +9,7 @@ fn main() {
     let p = Path::new(&title);
     let loaded = wisdom::import_from_file(p);
 
-    let mut plan = fftw3::Plan::r2c_1d(1 << n);
+    let mut plan = fftw3::Plan::r2c_1d(1 << n).unwrap();
     plan.execute();
 
     if !loaded { wisdom::export_to_file(p); }
@@ -22,7 +22,7 @@ fn main() {
     println!("{:?}", plan.output());
 
     let mut inverse = Planner::new()
-        .c2r(FftwVec::zeros(9), FftwVec::zeros(16))
+        .c2r(16, FftwVec::zeros(9), FftwVec::zeros(16))
         .plan()
         .ok().expect("failed to create plan");
     for (a, b) in inverse.input().iter_mut().zip(plan.output().unwrap().iter()) {
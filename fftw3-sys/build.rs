@@ -1,10 +1,258 @@
 extern crate pkg_config;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
 fn main() {
-    match pkg_config::find_library("fftw3") {
-        Ok(_) => return,
-        Err(_) => {}
+    if feature("DYNAMIC") {
+        if feature("VENDORED") || feature("STATIC") {
+            fail("the `dynamic` feature cannot be combined with `vendored` or `static`");
+        }
+        // everything is loaded at runtime.
+        return
+    }
+    if feature("VENDORED") {
+        build_vendored();
+        return
+    }
+
+    // the overrides change what is linked, so need to rebuild when
+    // they do.
+    println!("cargo:rerun-if-env-changed=FFTW3_DIR");
+    println!("cargo:rerun-if-env-changed=FFTW3_STATIC");
+    let statik = feature("STATIC") || match env::var("FFTW3_STATIC") {
+        Ok(v) => v != "0",
+        Err(_) => false,
+    };
+    let dir = env::var_os("FFTW3_DIR").map(PathBuf::from);
+
+    let mut search = vec![];
+    for &(lib, feat, cfg) in LIBRARIES.iter() {
+        if feat.map_or(true, feature) {
+            match dir {
+                Some(ref dir) => link_from_dir(dir, lib, statik),
+                None => search.extend(link_from_pkg_config(lib, statik).into_iter()),
+            }
+            println!("cargo:rustc-cfg={}", cfg);
+
+            // the threading support is a separate library per precision,
+            // without its own pkg-config file.
+            for &(suffix, feat) in [("_threads", "THREADS"), ("_omp", "OMP")].iter() {
+                if feature(feat) {
+                    let threads = format!("{}{}", lib, suffix);
+                    match dir {
+                        Some(ref dir) => link_from_dir(dir, &threads, statik),
+                        None => link_threads(&search, &threads, statik),
+                    }
+                }
+            }
+        }
+    }
+
+    if feature("THREADS") || feature("OMP") {
+        println!("cargo:rustc-cfg=fftw3_threads");
+    }
+    if feature("OMP") {
+        println!("cargo:rustc-link-lib=gomp");
+    }
+    if feature("THREADS") && statik {
+        println!("cargo:rustc-link-lib=pthread");
+    }
+    if feature("QUAD") && statik {
+        println!("cargo:rustc-link-lib=quadmath");
+    }
+}
+
+/// The libraries that can be linked, as (library name, cargo feature
+/// required, cfg to set).
+const LIBRARIES: &'static [(&'static str, Option<&'static str>, &'static str)] = &[
+    ("fftw3", None, "fftw3_double"),
+    ("fftw3f", Some("SINGLE"), "fftw3_single"),
+    ("fftw3l", Some("LONG"), "fftw3_long"),
+    ("fftw3q", Some("QUAD"), "fftw3_quad"),
+];
+
+fn feature(name: &str) -> bool {
+    env::var_os(&format!("CARGO_FEATURE_{}", name)).is_some()
+}
+
+fn kind(statik: bool) -> &'static str {
+    if statik { "static" } else { "dylib" }
+}
+
+/// Link `lib` from `$FFTW3_DIR/lib`.
+fn link_from_dir(dir: &Path, lib: &str, statik: bool) {
+    let libdir = dir.join("lib");
+    let file = if statik {
+        format!("lib{}.a", lib)
+    } else if cfg!(target_os = "macos") {
+        format!("lib{}.dylib", lib)
+    } else if cfg!(windows) {
+        format!("{}.lib", lib)
+    } else {
+        format!("lib{}.so", lib)
+    };
+    if fs::metadata(&libdir.join(&file)).is_err() {
+        fail(&format!("could not find {} ({} linking): {} does not exist in FFTW3_DIR",
+                      lib, kind(statik), libdir.join(&file).display()));
+    }
+
+    println!("cargo:rustc-link-search=native={}", libdir.display());
+    println!("cargo:rustc-link-lib={}={}", kind(statik), lib);
+}
+
+/// Link `lib` via pkg-config, returning the directories it is in.
+fn link_from_pkg_config(lib: &str, statik: bool) -> Vec<PathBuf> {
+    match pkg_config::Config::new().statik(statik).find(lib) {
+        Ok(l) => l.link_paths,
+        Err(e) => {
+            fail(&format!("could not find {} ({} linking) with pkg-config: {}\n\
+                           set FFTW3_DIR to the installation prefix, \
+                           or enable the `vendored` feature to build it from source.",
+                          lib, kind(statik), e))
+        }
     }
+}
+
+/// Link the threads library `lib`, which lives alongside the main
+/// libraries found in `search`.
+fn link_threads(search: &[PathBuf], lib: &str, statik: bool) {
+    let found = search.iter().any(|dir| {
+        fs::read_dir(dir).map(|entries| {
+            entries.filter_map(|e| e.ok()).any(|e| {
+                e.file_name().to_str().map_or(false, |f| f.starts_with(&format!("lib{}.", lib)))
+            })
+        }).unwrap_or(false)
+    });
+    if !search.is_empty() && !found {
+        fail(&format!("could not find {} ({} linking) in {:?}; it is not installed \
+                       or FFTW was built without threads", lib, kind(statik), search));
+    }
+    println!("cargo:rustc-link-lib={}={}", kind(statik), lib);
+}
+
+/// A precision to build, as (library name, configure flag, whether
+/// the SIMD options apply, cfg to set).
+const PRECISIONS: &'static [(&'static str, Option<&'static str>, bool, &'static str)] = &[
+    ("fftw3", None, true, "fftw3_double"),
+    ("fftw3f", Some("--enable-float"), true, "fftw3_single"),
+    ("fftw3l", Some("--enable-long-double"), false, "fftw3_long"),
+    ("fftw3q", Some("--enable-quad-precision"), false, "fftw3_quad"),
+];
+
+/// Compile the FFTW sources in `fftw/` (or `$FFTW3_SOURCE_DIR`) and
+/// link the results statically.
+fn build_vendored() {
+    println!("cargo:rerun-if-env-changed=FFTW3_SOURCE_DIR");
+    println!("cargo:rerun-if-env-changed=FFTW3_CONFIGURE_FLAGS");
+    let src = match env::var_os("FFTW3_SOURCE_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("fftw"),
+    };
+    if fs::metadata(&src.join("configure")).is_err() {
+        fail(&format!("the `vendored` feature requires an FFTW 3.3 release in {}, \
+                       but no `configure` script was found there: extract a release \
+                       tarball into that directory, or set FFTW3_SOURCE_DIR to one \
+                       (see fftw/README.md)",
+                      src.display()));
+    }
+    // only rebuild FFTW when its sources change, not whenever anything
+    // else in this package does.
+    println!("cargo:rerun-if-changed={}", src.display());
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let jobs = env::var("NUM_JOBS").unwrap_or("1".to_string());
+    let simd = simd_flags();
+
+    for &(lib, flag, use_simd, cfg) in PRECISIONS.iter() {
+        if lib == "fftw3q" && !feature("QUAD") {
+            continue
+        }
+        let build = out.join(format!("build-{}", lib));
+        let _ = fs::create_dir_all(&build);
+
+        let mut configure = Command::new(src.join("configure"));
+        configure.current_dir(&build)
+                 .arg(&format!("--prefix={}", out.display()))
+                 .arg("--enable-static")
+                 .arg("--disable-shared")
+                 .arg("--disable-fortran")
+                 .arg("--with-pic");
+        if feature("THREADS") {
+            configure.arg("--enable-threads");
+        }
+        if feature("OMP") {
+            configure.arg("--enable-openmp");
+        }
+        if let Some(flag) = flag {
+            configure.arg(flag);
+        }
+        if use_simd {
+            for f in simd.iter() {
+                // NEON is only supported in single precision.
+                if *f != "--enable-neon" || lib == "fftw3f" {
+                    configure.arg(f);
+                }
+            }
+        }
+        if let Ok(extra) = env::var("FFTW3_CONFIGURE_FLAGS") {
+            configure.args(&extra.split_whitespace().collect::<Vec<_>>());
+        }
+        run(&mut configure, lib);
+        run(Command::new("make").current_dir(&build).arg(&format!("-j{}", jobs)).arg("install"),
+            lib);
+
+        // the threads libraries depend on the main one, so come first.
+        for &(suffix, feat) in [("_threads", "THREADS"), ("_omp", "OMP")].iter() {
+            if feature(feat) {
+                println!("cargo:rustc-link-lib=static={}{}", lib, suffix);
+            }
+        }
+        println!("cargo:rustc-link-lib=static={}", lib);
+        println!("cargo:rustc-cfg={}", cfg);
+    }
+    println!("cargo:rustc-link-search=native={}", out.join("lib").display());
+    if feature("THREADS") || feature("OMP") {
+        println!("cargo:rustc-cfg=fftw3_threads");
+    }
+    if feature("OMP") {
+        println!("cargo:rustc-link-lib=gomp");
+    }
+    if feature("THREADS") {
+        println!("cargo:rustc-link-lib=pthread");
+    }
+    if feature("QUAD") {
+        println!("cargo:rustc-link-lib=quadmath");
+    }
+    println!("cargo:root={}", out.display());
+}
+
+/// The SIMD configure flags selected by cargo features.
+fn simd_flags() -> Vec<&'static str> {
+    let mut flags = vec![];
+    for &(name, flag) in [("SSE2", "--enable-sse2"),
+                          ("AVX", "--enable-avx"),
+                          ("AVX2", "--enable-avx2"),
+                          ("NEON", "--enable-neon")].iter() {
+        if feature(name) {
+            flags.push(flag)
+        }
+    }
+    flags
+}
+
+fn run(cmd: &mut Command, lib: &str) {
+    println!("running {:?}", cmd);
+    match cmd.status() {
+        Ok(s) if s.success() => {}
+        Ok(s) => fail(&format!("building {} failed: {:?} exited with {}", lib, cmd, s)),
+        Err(e) => fail(&format!("building {} failed: could not run {:?}: {}", lib, cmd, e)),
+    }
+}
 
-    println!("failed to find libfftw3, and automatically building it is currently unimplemented.");
+fn fail(msg: &str) -> ! {
+    println!("{}", msg);
     std::process::exit(1);
 }
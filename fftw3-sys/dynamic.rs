@@ -0,0 +1,325 @@
+//! Loading FFTW at runtime.
+//!
+//! With the `dynamic` feature, no FFTW library is linked at build
+//! time. Instead, each function is looked up in a shared library
+//! loaded with `dlopen` the first time it is called. A library can be
+//! selected explicitly with `load`, before calling any FFTW function;
+//! otherwise the path in the `FFTW3_LIB` (or `FFTW3F_LIB`,
+//! `FFTW3L_LIB`, `FFTW3Q_LIB`) environment variable is used, falling
+//! back to the system's library.
+//!
+//! The threads functions usually live in a separate library, such as
+//! `libfftw3_threads.so`. They are looked up in the main library
+//! first, in case it includes them, and otherwise in the library
+//! given to `load_threads`, or in the `FFTW3_THREADS_LIB` (etc.)
+//! environment variable, falling back to the system's.
+
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::env;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use libc::{c_char, c_int, c_void};
+
+#[cfg(target_os = "linux")]
+#[link(name = "dl")]
+extern "C" {}
+
+extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *mut c_char;
+}
+
+#[cfg(target_os = "macos")]
+const RTLD_FLAGS: c_int = 0x2 | 0x8;
+#[cfg(not(target_os = "macos"))]
+const RTLD_FLAGS: c_int = 0x2 | 0x100;
+
+/// The suffix of a versioned shared library, e.g. `libfftw3.3.dylib`.
+#[cfg(target_os = "macos")]
+const LIBRARY_SUFFIX: &'static str = ".3.dylib";
+#[cfg(not(target_os = "macos"))]
+const LIBRARY_SUFFIX: &'static str = ".so.3";
+
+/// Functions added after FFTW 3.3.0, which a library need not have to
+/// be loaded: they are only looked up when first called.
+static LATER_SYMBOLS: &'static [&'static str] = &[
+    // 3.3.5
+    "make_planner_thread_safe",
+    "set_planner_hooks",
+    // 3.3.9
+    "planner_nthreads",
+    "threads_set_callback",
+];
+
+/// The precisions of FFTW, each of which is a separate set of
+/// functions (and, usually, a separate library).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Precision {
+    Double,
+    Single,
+    Long,
+    Quad,
+}
+
+impl Precision {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The prefix of this precision's functions, e.g. `fftwf_`.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Precision::Double => "fftw_",
+            Precision::Single => "fftwf_",
+            Precision::Long => "fftwl_",
+            Precision::Quad => "fftwq_",
+        }
+    }
+
+    fn of_symbol(name: &str) -> Precision {
+        if name.starts_with("fftwf_") {
+            Precision::Single
+        } else if name.starts_with("fftwl_") {
+            Precision::Long
+        } else if name.starts_with("fftwq_") {
+            Precision::Quad
+        } else {
+            Precision::Double
+        }
+    }
+
+    fn default_library(self) -> (&'static str, String) {
+        let (var, name) = match self {
+            Precision::Double => ("FFTW3_LIB", "fftw3"),
+            Precision::Single => ("FFTW3F_LIB", "fftw3f"),
+            Precision::Long => ("FFTW3L_LIB", "fftw3l"),
+            Precision::Quad => ("FFTW3Q_LIB", "fftw3q"),
+        };
+        (var, format!("lib{}{}", name, LIBRARY_SUFFIX))
+    }
+
+    fn default_threads_library(self) -> (&'static str, String) {
+        let (var, name) = match self {
+            Precision::Double => ("FFTW3_THREADS_LIB", "fftw3_threads"),
+            Precision::Single => ("FFTW3F_THREADS_LIB", "fftw3f_threads"),
+            Precision::Long => ("FFTW3L_THREADS_LIB", "fftw3l_threads"),
+            Precision::Quad => ("FFTW3Q_THREADS_LIB", "fftw3q_threads"),
+        };
+        (var, format!("lib{}{}", name, LIBRARY_SUFFIX))
+    }
+}
+
+fn is_threads_symbol(name: &str) -> bool {
+    name.contains("thread")
+}
+
+fn is_later_symbol(name: &str) -> bool {
+    let name = &name[Precision::of_symbol(name).prefix().len()..];
+    LATER_SYMBOLS.iter().any(|s| *s == name)
+}
+
+/// A failure to load an FFTW library.
+#[derive(Debug)]
+pub enum LoadError {
+    /// `dlopen` failed, with the given message.
+    Open(String),
+    /// The library does not provide every function of a requested
+    /// precision.
+    MissingSymbols(Precision, Vec<&'static str>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Open(ref msg) => write!(f, "could not load FFTW: {}", msg),
+            LoadError::MissingSymbols(p, ref syms) => {
+                write!(f, "library is missing {} symbol(s) for {:?} precision: {}",
+                       syms.len(), p, syms.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Open(_) => "could not load FFTW",
+            LoadError::MissingSymbols(..) => "library is missing FFTW symbols",
+        }
+    }
+}
+
+static HANDLES: [AtomicUsize; 4] = [ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT,
+                                    ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT];
+static THREADS_HANDLES: [AtomicUsize; 4] = [ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT,
+                                            ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT];
+
+/// Load the library at `path`, and use it for each of `precisions`.
+///
+/// Every function of FFTW 3.3.0 of each of the precisions (other
+/// than those from the threads library, which are loaded separately)
+/// must be present. Later additions are looked up when first called,
+/// which panics if they are missing.
+///
+/// This must be called before any function of those precisions is
+/// used, since the look-ups are cached.
+pub fn load(path: &Path, precisions: &[Precision]) -> Result<(), LoadError> {
+    let handle = try!(open_checked(path, precisions, false));
+    for &p in precisions.iter() {
+        HANDLES[p.index()].store(handle as usize, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+/// Load the threads library at `path`, and use it for each of
+/// `precisions`.
+///
+/// Every threads function of FFTW 3.3.0 of each of the precisions
+/// must be present.
+/// Like `load`, this must be called before any of them is used.
+pub fn load_threads(path: &Path, precisions: &[Precision]) -> Result<(), LoadError> {
+    let handle = try!(open_checked(path, precisions, true));
+    for &p in precisions.iter() {
+        THREADS_HANDLES[p.index()].store(handle as usize, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+/// Open `path`, checking that it has every 3.3.0 function (or every
+/// threads function, if `threads`) of each of `precisions`.
+fn open_checked(path: &Path, precisions: &[Precision],
+                threads: bool) -> Result<*mut c_void, LoadError> {
+    let handle = try!(open(path.to_str().unwrap_or("")));
+    for &p in precisions.iter() {
+        let missing = missing_symbols(handle, p, threads);
+        if !missing.is_empty() {
+            return Err(LoadError::MissingSymbols(p, missing))
+        }
+    }
+    Ok(handle)
+}
+
+fn missing_symbols(handle: *mut c_void, p: Precision, threads: bool) -> Vec<&'static str> {
+    ::SYMBOLS.iter()
+        .filter(|s| Precision::of_symbol(s) == p && is_threads_symbol(s) == threads)
+        .filter(|s| !is_later_symbol(s))
+        .filter(|s| unsafe {symbol(handle, s).is_null()})
+        .map(|s| *s)
+        .collect()
+}
+
+/// Whether a library has been loaded for `p`.
+pub fn is_loaded(p: Precision) -> bool {
+    HANDLES[p.index()].load(Ordering::SeqCst) != 0
+}
+
+/// Whether the threads functions of double precision are available,
+/// loading their library if necessary.
+pub fn has_threads() -> bool {
+    is_loaded(Precision::Double) && threads_handle(Precision::Double).is_ok()
+}
+
+fn open(path: &str) -> Result<*mut c_void, LoadError> {
+    let c = try!(CString::new(path).map_err(|_| LoadError::Open(format!("invalid path {:?}", path))));
+    unsafe {
+        let handle = dlopen(c.as_ptr(), RTLD_FLAGS);
+        if handle.is_null() {
+            Err(LoadError::Open(last_error()))
+        } else {
+            Ok(handle)
+        }
+    }
+}
+
+unsafe fn symbol(handle: *mut c_void, name: &str) -> *mut c_void {
+    let c = CString::new(name).unwrap();
+    dlsym(handle, c.as_ptr())
+}
+
+fn last_error() -> String {
+    unsafe {
+        let e = dlerror();
+        if e.is_null() {
+            "unknown error".to_string()
+        } else {
+            String::from_utf8_lossy(CStr::from_ptr(e).to_bytes()).into_owned()
+        }
+    }
+}
+
+/// The handle for `p`, loading the default library if necessary.
+fn handle(p: Precision) -> *mut c_void {
+    let h = HANDLES[p.index()].load(Ordering::SeqCst);
+    if h != 0 {
+        return h as *mut c_void
+    }
+
+    let (var, default) = p.default_library();
+    let path = env::var(var).unwrap_or(default);
+    match load(Path::new(&path), &[p]) {
+        Ok(()) => HANDLES[p.index()].load(Ordering::SeqCst) as *mut c_void,
+        Err(e) => panic!("fftw3-sys: loading {:?} precision FFTW from {}: {}", p, path, e),
+    }
+}
+
+/// The handle providing the threads functions of `p`: the main
+/// library if it has them, or else a separate threads library,
+/// loaded if necessary.
+fn threads_handle(p: Precision) -> Result<*mut c_void, String> {
+    let h = THREADS_HANDLES[p.index()].load(Ordering::SeqCst);
+    if h != 0 {
+        return Ok(h as *mut c_void)
+    }
+
+    let main = handle(p);
+    if missing_symbols(main, p, true).is_empty() {
+        THREADS_HANDLES[p.index()].store(main as usize, Ordering::SeqCst);
+        return Ok(main)
+    }
+    let (var, default) = p.default_threads_library();
+    let path = env::var(var).unwrap_or(default);
+    match load_threads(Path::new(&path), &[p]) {
+        Ok(()) => Ok(THREADS_HANDLES[p.index()].load(Ordering::SeqCst) as *mut c_void),
+        Err(e) => Err(format!("loading {:?} precision FFTW threads from {} (set {} to \
+                               its path): {}", p, path, var, e)),
+    }
+}
+
+/// Look up the symbol `name` from the library for its precision (or
+/// its threads library).
+///
+/// Panics if it cannot be found.
+#[doc(hidden)]
+pub fn resolve(name: &str) -> usize {
+    let p = Precision::of_symbol(name);
+    let handle = if is_threads_symbol(name) {
+        match threads_handle(p) {
+            Ok(h) => h,
+            Err(e) => panic!("fftw3-sys: {}", e),
+        }
+    } else {
+        handle(p)
+    };
+    let f = unsafe {symbol(handle, name)};
+    if f.is_null() {
+        panic!("fftw3-sys: symbol `{}` not found in the {:?} precision library: {}",
+               name, p, last_error())
+    }
+    f as usize
+}
+
+/// Look up a `const char[]` variable such as `fftw_version`.
+pub fn string(name: &str) -> Option<&'static CStr> {
+    let p = Precision::of_symbol(name);
+    unsafe {
+        let s = symbol(handle(p), name);
+        if s.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(s as *const c_char))
+        }
+    }
+}
@@ -4,11 +4,57 @@
 extern crate libc;
 use libc::*;
 
+#[cfg(not(feature = "dynamic"))]
+macro_rules! fftw_functions {
+    ($(pub fn $name: ident($($arg: ident: $t: ty),*) $(-> $ret: ty)*;)*) => {
+        extern "C" {
+            $(pub fn $name($($arg: $t),*) $(-> $ret)*;)*
+        }
+    }
+}
+
+#[cfg(feature = "dynamic")]
+macro_rules! fftw_functions {
+    ($(pub fn $name: ident($($arg: ident: $t: ty),*) $(-> $ret: ty)*;)*) => {
+        $(
+            pub unsafe fn $name($($arg: $t),*) $(-> $ret)* {
+                use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+                static PTR: AtomicUsize = ATOMIC_USIZE_INIT;
+
+                let mut f = PTR.load(Ordering::Relaxed);
+                if f == 0 {
+                    f = ::dynamic::resolve(stringify!($name));
+                    PTR.store(f, Ordering::Relaxed);
+                }
+                let f: extern "C" fn($($t),*) $(-> $ret)* = ::std::mem::transmute(f);
+                f($($arg),*)
+            }
+        )*
+
+        /// Every function in the bindings, for checking a library.
+        pub static SYMBOLS: &'static [&'static str] = &[$(stringify!($name)),*];
+    }
+}
+
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
+
 pub static FFTW_FORWARD: c_int = -1;
 pub static FFTW_BACKWARD: c_int = 1;
 
 pub static FFTW_NO_TIMELIMIT: f64 = -1.0;
 
+/// Whether the double precision library (`fftw3`) is linked.
+pub const LINKED_DOUBLE: bool = cfg!(fftw3_double);
+/// Whether the single precision library (`fftw3f`) is linked.
+pub const LINKED_SINGLE: bool = cfg!(fftw3_single);
+/// Whether the long double precision library (`fftw3l`) is linked.
+pub const LINKED_LONG: bool = cfg!(fftw3_long);
+/// Whether the quad precision library (`fftw3q`) is linked.
+pub const LINKED_QUAD: bool = cfg!(fftw3_quad);
+/// Whether a threads library (`fftw3_threads` or `fftw3_omp`) is linked.
+pub const LINKED_THREADS: bool = cfg!(fftw3_threads);
+
 pub static FFTW_MEASURE: c_uint = 0;
 pub static FFTW_DESTROY_INPUT: c_uint = 1 << 0;
 pub static FFTW_UNALIGNED: c_uint = 1 << 1;
@@ -45,8 +91,13 @@ pub struct Struct_fftw_iodim64_do_not_use_me {
     pub is: ptrdiff_t,
     pub os: ptrdiff_t,
 }
+pub type fftw_voidfunc = extern "C" fn();
+pub type fftw_parallel_loop_func =
+    extern "C" fn(work: extern "C" fn(*mut c_char) -> *mut c_void,
+                  jobdata: *mut c_char, elsize: size_t, njobs: c_int,
+                  data: *mut c_void);
 pub type fftw_write_char_func_do_not_use_me =
-    extern "C" fn(arg1: c_schar, arg2: *mut c_void);
+    extern "C" fn(arg1: c_char, arg2: *mut c_void);
 pub type fftw_read_char_func_do_not_use_me =
     extern "C" fn(arg1: *mut c_void) -> c_int;
 pub type fftw_complex = [c_double; 2];
@@ -65,6 +116,9 @@ pub type fftwf_iodim64 = Struct_fftw_iodim64_do_not_use_me;
 pub type fftwf_r2r_kind = Enum_fftw_r2r_kind_do_not_use_me;
 pub type fftwf_write_char_func = fftw_write_char_func_do_not_use_me;
 pub type fftwf_read_char_func = fftw_read_char_func_do_not_use_me;
+// Rust has no `long double`, so this only has the right layout
+// where that is the same as `double` (e.g. MSVC); `fftwl_*` should
+// otherwise only be used with pointers.
 pub type fftwl_complex = [c_double; 2];
 pub type Struct_fftwl_plan_s = c_void;
 pub type fftwl_plan = *mut Struct_fftwl_plan_s;
@@ -73,19 +127,43 @@ pub type fftwl_iodim64 = Struct_fftw_iodim64_do_not_use_me;
 pub type fftwl_r2r_kind = Enum_fftw_r2r_kind_do_not_use_me;
 pub type fftwl_write_char_func = fftw_write_char_func_do_not_use_me;
 pub type fftwl_read_char_func = fftw_read_char_func_do_not_use_me;
+/// GCC's `__float128`, which has no Rust equivalent and so can only be
+/// used behind pointers.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct __float128 {
+    bits: [u64; 2],
+}
+pub type fftwq_complex = [__float128; 2];
+pub type Struct_fftwq_plan_s = c_void;
+pub type fftwq_plan = *mut Struct_fftwq_plan_s;
+pub type fftwq_iodim = Struct_fftw_iodim_do_not_use_me;
+pub type fftwq_iodim64 = Struct_fftw_iodim64_do_not_use_me;
+pub type fftwq_r2r_kind = Enum_fftw_r2r_kind_do_not_use_me;
+pub type fftwq_write_char_func = fftw_write_char_func_do_not_use_me;
+pub type fftwq_read_char_func = fftw_read_char_func_do_not_use_me;
 
+#[cfg(not(feature = "dynamic"))]
 extern "C" {
-    pub static mut fftw_version: c_void;
-    pub static mut fftw_cc: c_void;
-    pub static mut fftw_codelet_optim: c_void;
-    pub static mut fftwf_version: c_void;
-    pub static mut fftwf_cc: c_void;
-    pub static mut fftwf_codelet_optim: c_void;
-    pub static mut fftwl_version: c_void;
-    pub static mut fftwl_cc: c_void;
-    pub static mut fftwl_codelet_optim: c_void;
+    // these are `const char[]`, so the address of each is the start
+    // of a nul-terminated string.
+    pub static fftw_version: [c_char; 0];
+    pub static fftw_cc: [c_char; 0];
+    pub static fftw_codelet_optim: [c_char; 0];
+    pub static fftwf_version: [c_char; 0];
+    pub static fftwf_cc: [c_char; 0];
+    pub static fftwf_codelet_optim: [c_char; 0];
+    pub static fftwl_version: [c_char; 0];
+    pub static fftwl_cc: [c_char; 0];
+    pub static fftwl_codelet_optim: [c_char; 0];
+    pub static fftwq_version: [c_char; 0];
+    pub static fftwq_cc: [c_char; 0];
+    pub static fftwq_codelet_optim: [c_char; 0];
+}
+
+fftw_functions! {
     pub fn fftw_execute(p: fftw_plan);
-    pub fn fftw_plan_dft(rank: c_int, n: *mut c_int, _in: *mut fftw_complex,
+    pub fn fftw_plan_dft(rank: c_int, n: *const c_int, _in: *mut fftw_complex,
                          out: *mut fftw_complex, sign: c_int, flags: c_uint)
      -> fftw_plan;
     pub fn fftw_plan_dft_1d(n: c_int, _in: *mut fftw_complex,
@@ -258,21 +336,30 @@ extern "C" {
     pub fn fftw_cleanup();
     pub fn fftw_set_timelimit(t: c_double);
     pub fn fftw_plan_with_nthreads(nthreads: c_int);
+    pub fn fftw_planner_nthreads() -> c_int;
     pub fn fftw_init_threads() -> c_int;
     pub fn fftw_cleanup_threads();
-    pub fn fftw_export_wisdom_to_filename(filename: *const c_schar) -> c_int;
+    pub fn fftw_make_planner_thread_safe();
+    pub fn fftw_threads_set_callback(parallel_loop: fftw_parallel_loop_func,
+                                     data: *mut c_void);
+    pub fn fftw_set_planner_hooks(before: Option<fftw_voidfunc>,
+                                  after: Option<fftw_voidfunc>);
+    pub fn fftw_export_wisdom_to_filename(filename: *const c_char) -> c_int;
     pub fn fftw_export_wisdom_to_file(output_file: *mut FILE);
-    pub fn fftw_export_wisdom_to_string() -> *mut c_schar;
+    // the returned string must be freed with `fftw_free`.
+    pub fn fftw_export_wisdom_to_string() -> *mut c_char;
     pub fn fftw_export_wisdom(write_char: fftw_write_char_func,
                               data: *mut c_void);
     pub fn fftw_import_system_wisdom() -> c_int;
-    pub fn fftw_import_wisdom_from_filename(filename: *const c_schar) -> c_int;
+    pub fn fftw_import_wisdom_from_filename(filename: *const c_char) -> c_int;
     pub fn fftw_import_wisdom_from_file(input_file: *mut FILE) -> c_int;
-    pub fn fftw_import_wisdom_from_string(input_string: *const c_schar) -> c_int;
+    pub fn fftw_import_wisdom_from_string(input_string: *const c_char) -> c_int;
     pub fn fftw_import_wisdom(read_char: fftw_read_char_func,
                               data: *mut c_void) -> c_int;
     pub fn fftw_fprint_plan(p: fftw_plan, output_file: *mut FILE);
     pub fn fftw_print_plan(p: fftw_plan);
+    // the returned string must be freed with `fftw_free`.
+    pub fn fftw_sprint_plan(p: fftw_plan) -> *mut c_char;
     pub fn fftw_malloc(n: size_t) -> *mut c_void;
     pub fn fftw_alloc_real(n: size_t) -> *mut c_double;
     pub fn fftw_alloc_complex(n: size_t) -> *mut fftw_complex;
@@ -281,6 +368,7 @@ extern "C" {
                       fmas: *mut c_double);
     pub fn fftw_estimate_cost(p: fftw_plan) -> c_double;
     pub fn fftw_cost(p: fftw_plan) -> c_double;
+    pub fn fftw_alignment_of(p: *mut c_double) -> c_int;
     pub fn fftwf_execute(p: fftwf_plan);
     pub fn fftwf_plan_dft(rank: c_int, n: *const c_int, _in: *mut fftwf_complex,
                           out: *mut fftwf_complex, sign: c_int, flags: c_uint)
@@ -458,21 +546,30 @@ extern "C" {
     pub fn fftwf_cleanup();
     pub fn fftwf_set_timelimit(t: c_double);
     pub fn fftwf_plan_with_nthreads(nthreads: c_int);
+    pub fn fftwf_planner_nthreads() -> c_int;
     pub fn fftwf_init_threads() -> c_int;
     pub fn fftwf_cleanup_threads();
-    pub fn fftwf_export_wisdom_to_filename(filename: *const c_schar) -> c_int;
+    pub fn fftwf_make_planner_thread_safe();
+    pub fn fftwf_threads_set_callback(parallel_loop: fftw_parallel_loop_func,
+                                      data: *mut c_void);
+    pub fn fftwf_set_planner_hooks(before: Option<fftw_voidfunc>,
+                                   after: Option<fftw_voidfunc>);
+    pub fn fftwf_export_wisdom_to_filename(filename: *const c_char) -> c_int;
     pub fn fftwf_export_wisdom_to_file(output_file: *mut FILE);
-    pub fn fftwf_export_wisdom_to_string() -> *mut c_schar;
+    // the returned string must be freed with `fftwf_free`.
+    pub fn fftwf_export_wisdom_to_string() -> *mut c_char;
     pub fn fftwf_export_wisdom(write_char: fftwf_write_char_func,
                                data: *mut c_void);
     pub fn fftwf_import_system_wisdom() -> c_int;
-    pub fn fftwf_import_wisdom_from_filename(filename: *const c_schar) -> c_int;
+    pub fn fftwf_import_wisdom_from_filename(filename: *const c_char) -> c_int;
     pub fn fftwf_import_wisdom_from_file(input_file: *mut FILE) -> c_int;
-    pub fn fftwf_import_wisdom_from_string(input_string: *const c_schar) -> c_int;
+    pub fn fftwf_import_wisdom_from_string(input_string: *const c_char) -> c_int;
     pub fn fftwf_import_wisdom(read_char: fftwf_read_char_func,
                                data: *mut c_void) -> c_int;
     pub fn fftwf_fprint_plan(p: fftwf_plan, output_file: *mut FILE);
     pub fn fftwf_print_plan(p: fftwf_plan);
+    // the returned string must be freed with `fftwf_free`.
+    pub fn fftwf_sprint_plan(p: fftwf_plan) -> *mut c_char;
     pub fn fftwf_malloc(n: size_t) -> *mut c_void;
     pub fn fftwf_alloc_real(n: size_t) -> *mut c_float;
     pub fn fftwf_alloc_complex(n: size_t) -> *mut fftwf_complex;
@@ -481,6 +578,7 @@ extern "C" {
                        fmas: *mut c_double);
     pub fn fftwf_estimate_cost(p: fftwf_plan) -> c_double;
     pub fn fftwf_cost(p: fftwf_plan) -> c_double;
+    pub fn fftwf_alignment_of(p: *mut c_float) -> c_int;
     pub fn fftwl_execute(p: fftwl_plan);
     pub fn fftwl_plan_dft(rank: c_int, n: *const c_int, _in: *mut fftwl_complex,
                           out: *mut fftwl_complex, sign: c_int, flags: c_uint)
@@ -663,21 +761,30 @@ extern "C" {
     pub fn fftwl_cleanup();
     pub fn fftwl_set_timelimit(t: c_double);
     pub fn fftwl_plan_with_nthreads(nthreads: c_int);
+    pub fn fftwl_planner_nthreads() -> c_int;
     pub fn fftwl_init_threads() -> c_int;
     pub fn fftwl_cleanup_threads();
-    pub fn fftwl_export_wisdom_to_filename(filename: *const c_schar) -> c_int;
+    pub fn fftwl_make_planner_thread_safe();
+    pub fn fftwl_threads_set_callback(parallel_loop: fftw_parallel_loop_func,
+                                      data: *mut c_void);
+    pub fn fftwl_set_planner_hooks(before: Option<fftw_voidfunc>,
+                                   after: Option<fftw_voidfunc>);
+    pub fn fftwl_export_wisdom_to_filename(filename: *const c_char) -> c_int;
     pub fn fftwl_export_wisdom_to_file(output_file: *mut FILE);
-    pub fn fftwl_export_wisdom_to_string() -> *mut c_schar;
+    // the returned string must be freed with `fftwl_free`.
+    pub fn fftwl_export_wisdom_to_string() -> *mut c_char;
     pub fn fftwl_export_wisdom(write_char: fftwl_write_char_func,
                                data: *mut c_void);
     pub fn fftwl_import_system_wisdom() -> c_int;
-    pub fn fftwl_import_wisdom_from_filename(filename: *const c_schar) -> c_int;
+    pub fn fftwl_import_wisdom_from_filename(filename: *const c_char) -> c_int;
     pub fn fftwl_import_wisdom_from_file(input_file: *mut FILE) -> c_int;
-    pub fn fftwl_import_wisdom_from_string(input_string: *const c_schar) -> c_int;
+    pub fn fftwl_import_wisdom_from_string(input_string: *const c_char) -> c_int;
     pub fn fftwl_import_wisdom(read_char: fftwl_read_char_func,
                                data: *mut c_void) -> c_int;
     pub fn fftwl_fprint_plan(p: fftwl_plan, output_file: *mut FILE);
     pub fn fftwl_print_plan(p: fftwl_plan);
+    // the returned string must be freed with `fftwl_free`.
+    pub fn fftwl_sprint_plan(p: fftwl_plan) -> *mut c_char;
     pub fn fftwl_malloc(n: size_t) -> *mut c_void;
     pub fn fftwl_alloc_real(n: size_t) -> *mut c_double;
     pub fn fftwl_alloc_complex(n: size_t) -> *mut fftwl_complex;
@@ -686,4 +793,254 @@ extern "C" {
                        fmas: *mut c_double);
     pub fn fftwl_estimate_cost(p: fftwl_plan) -> c_double;
     pub fn fftwl_cost(p: fftwl_plan) -> c_double;
+    pub fn fftwl_alignment_of(p: *mut c_double) -> c_int;
+    pub fn fftwq_execute(p: fftwq_plan);
+    pub fn fftwq_plan_dft(rank: c_int, n: *const c_int, _in: *mut fftwq_complex,
+                          out: *mut fftwq_complex, sign: c_int, flags: c_uint)
+     -> fftwq_plan;
+    pub fn fftwq_plan_dft_1d(n: c_int, _in: *mut fftwq_complex,
+                             out: *mut fftwq_complex, sign: c_int,
+                             flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_dft_2d(n0: c_int, n1: c_int, _in: *mut fftwq_complex,
+                             out: *mut fftwq_complex, sign: c_int,
+                             flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_dft_3d(n0: c_int, n1: c_int, n2: c_int,
+                             _in: *mut fftwq_complex, out: *mut fftwq_complex,
+                             sign: c_int, flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_many_dft(rank: c_int, n: *const c_int, howmany: c_int,
+                               _in: *mut fftwq_complex, inembed: *const c_int,
+                               istride: c_int, idist: c_int,
+                               out: *mut fftwq_complex, onembed: *const c_int,
+                               ostride: c_int, odist: c_int, sign: c_int,
+                               flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru_dft(rank: c_int, dims: *const fftwq_iodim,
+                               howmany_rank: c_int, howmany_dims: *const fftwq_iodim,
+                               _in: *mut fftwq_complex, out: *mut fftwq_complex,
+                               sign: c_int, flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru_split_dft(rank: c_int, dims: *const fftwq_iodim,
+                                     howmany_rank: c_int,
+                                     howmany_dims: *const fftwq_iodim,
+                                     ri: *mut __float128, ii: *mut __float128,
+                                     ro: *mut __float128, io: *mut __float128,
+                                     flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru64_dft(rank: c_int, dims: *const fftwq_iodim64,
+                                 howmany_rank: c_int,
+                                 howmany_dims: *const fftwq_iodim64,
+                                 _in: *mut fftwq_complex,
+                                 out: *mut fftwq_complex, sign: c_int,
+                                 flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru64_split_dft(rank: c_int, dims: *const fftwq_iodim64,
+                                       howmany_rank: c_int,
+                                       howmany_dims: *const fftwq_iodim64,
+                                       ri: *mut __float128, ii: *mut __float128,
+                                       ro: *mut __float128, io: *mut __float128,
+                                       flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_execute_dft(p: fftwq_plan, _in: *mut fftwq_complex,
+                             out: *mut fftwq_complex);
+    pub fn fftwq_execute_split_dft(p: fftwq_plan, ri: *mut __float128,
+                                   ii: *mut __float128, ro: *mut __float128,
+                                   io: *mut __float128);
+    pub fn fftwq_plan_many_dft_r2c(rank: c_int, n: *const c_int, howmany: c_int,
+                                   _in: *mut __float128, inembed: *const c_int,
+                                   istride: c_int, idist: c_int,
+                                   out: *mut fftwq_complex, onembed: *const c_int,
+                                   ostride: c_int, odist: c_int, flags: c_uint)
+     -> fftwq_plan;
+    pub fn fftwq_plan_dft_r2c(rank: c_int, n: *const c_int, _in: *mut __float128,
+                              out: *mut fftwq_complex, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_dft_r2c_1d(n: c_int, _in: *mut __float128,
+                                 out: *mut fftwq_complex, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_dft_r2c_2d(n0: c_int, n1: c_int, _in: *mut __float128,
+                                 out: *mut fftwq_complex, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_dft_r2c_3d(n0: c_int, n1: c_int, n2: c_int,
+                                 _in: *mut __float128, out: *mut fftwq_complex,
+                                 flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_many_dft_c2r(rank: c_int, n: *const c_int, howmany: c_int,
+                                   _in: *mut fftwq_complex, inembed: *const c_int,
+                                   istride: c_int, idist: c_int,
+                                   out: *mut __float128, onembed: *const c_int,
+                                   ostride: c_int, odist: c_int, flags: c_uint)
+     -> fftwq_plan;
+    pub fn fftwq_plan_dft_c2r(rank: c_int, n: *const c_int, _in: *mut fftwq_complex,
+                              out: *mut __float128, flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_dft_c2r_1d(n: c_int, _in: *mut fftwq_complex,
+                                 out: *mut __float128, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_dft_c2r_2d(n0: c_int, n1: c_int, _in: *mut fftwq_complex,
+                                 out: *mut __float128, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_dft_c2r_3d(n0: c_int, n1: c_int, n2: c_int,
+                                 _in: *mut fftwq_complex, out: *mut __float128,
+                                 flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru_dft_r2c(rank: c_int, dims: *const fftwq_iodim,
+                                   howmany_rank: c_int,
+                                   howmany_dims: *const fftwq_iodim,
+                                   _in: *mut __float128, out: *mut fftwq_complex,
+                                   flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru_dft_c2r(rank: c_int, dims: *const fftwq_iodim,
+                                   howmany_rank: c_int,
+                                   howmany_dims: *const fftwq_iodim,
+                                   _in: *mut fftwq_complex, out: *mut __float128,
+                                   flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru_split_dft_r2c(rank: c_int, dims: *const fftwq_iodim,
+                                         howmany_rank: c_int,
+                                         howmany_dims: *const fftwq_iodim,
+                                         _in: *mut __float128, ro: *mut __float128,
+                                         io: *mut __float128, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_guru_split_dft_c2r(rank: c_int, dims: *const fftwq_iodim,
+                                         howmany_rank: c_int,
+                                         howmany_dims: *const fftwq_iodim,
+                                         ri: *mut __float128, ii: *mut __float128,
+                                         out: *mut __float128, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_guru64_dft_r2c(rank: c_int, dims: *const fftwq_iodim64,
+                                     howmany_rank: c_int,
+                                     howmany_dims: *const fftwq_iodim64,
+                                     _in: *mut __float128,
+                                     out: *mut fftwq_complex, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_guru64_dft_c2r(rank: c_int, dims: *const fftwq_iodim64,
+                                     howmany_rank: c_int,
+                                     howmany_dims: *const fftwq_iodim64,
+                                     _in: *mut fftwq_complex,
+                                     out: *mut __float128, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_guru64_split_dft_r2c(rank: c_int, dims: *const fftwq_iodim64,
+                                           howmany_rank: c_int,
+                                           howmany_dims: *const fftwq_iodim64,
+                                           _in: *mut __float128,
+                                           ro: *mut __float128,
+                                           io: *mut __float128, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_guru64_split_dft_c2r(rank: c_int, dims: *const fftwq_iodim64,
+                                           howmany_rank: c_int,
+                                           howmany_dims: *const fftwq_iodim64,
+                                           ri: *mut __float128,
+                                           ii: *mut __float128,
+                                           out: *mut __float128, flags: c_uint)
+     -> fftwq_plan;
+    pub fn fftwq_execute_dft_r2c(p: fftwq_plan, _in: *mut __float128,
+                                 out: *mut fftwq_complex);
+    pub fn fftwq_execute_dft_c2r(p: fftwq_plan, _in: *mut fftwq_complex,
+                                 out: *mut __float128);
+    pub fn fftwq_execute_split_dft_r2c(p: fftwq_plan, _in: *mut __float128,
+                                       ro: *mut __float128, io: *mut __float128);
+    pub fn fftwq_execute_split_dft_c2r(p: fftwq_plan, ri: *mut __float128,
+                                       ii: *mut __float128, out: *mut __float128);
+    pub fn fftwq_plan_many_r2r(rank: c_int, n: *const c_int, howmany: c_int,
+                               _in: *mut __float128, inembed: *const c_int,
+                               istride: c_int, idist: c_int,
+                               out: *mut __float128, onembed: *const c_int,
+                               ostride: c_int, odist: c_int,
+                               kind: *const fftwq_r2r_kind, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_r2r(rank: c_int, n: *const c_int, _in: *mut __float128,
+                          out: *mut __float128, kind: *const fftwq_r2r_kind,
+                          flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_r2r_1d(n: c_int, _in: *mut __float128, out: *mut __float128,
+                             kind: fftwq_r2r_kind, flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_r2r_2d(n0: c_int, n1: c_int, _in: *mut __float128,
+                             out: *mut __float128, kind0: fftwq_r2r_kind,
+                             kind1: fftwq_r2r_kind, flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_r2r_3d(n0: c_int, n1: c_int, n2: c_int,
+                             _in: *mut __float128, out: *mut __float128,
+                             kind0: fftwq_r2r_kind, kind1: fftwq_r2r_kind,
+                             kind2: fftwq_r2r_kind, flags: c_uint) -> fftwq_plan;
+    pub fn fftwq_plan_guru_r2r(rank: c_int, dims: *const fftwq_iodim,
+                               howmany_rank: c_int, howmany_dims: *const fftwq_iodim,
+                               _in: *mut __float128, out: *mut __float128,
+                               kind: *const fftwq_r2r_kind, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_plan_guru64_r2r(rank: c_int, dims: *const fftwq_iodim64,
+                                 howmany_rank: c_int,
+                                 howmany_dims: *const fftwq_iodim64,
+                                 _in: *mut __float128, out: *mut __float128,
+                                 kind: *const fftwq_r2r_kind, flags: c_uint) ->
+      fftwq_plan;
+    pub fn fftwq_execute_r2r(p: fftwq_plan, _in: *mut __float128,
+                             out: *mut __float128);
+    pub fn fftwq_destroy_plan(p: fftwq_plan);
+    pub fn fftwq_forget_wisdom();
+    pub fn fftwq_cleanup();
+    pub fn fftwq_set_timelimit(t: c_double);
+    pub fn fftwq_plan_with_nthreads(nthreads: c_int);
+    pub fn fftwq_planner_nthreads() -> c_int;
+    pub fn fftwq_init_threads() -> c_int;
+    pub fn fftwq_cleanup_threads();
+    pub fn fftwq_make_planner_thread_safe();
+    pub fn fftwq_threads_set_callback(parallel_loop: fftw_parallel_loop_func,
+                                      data: *mut c_void);
+    pub fn fftwq_set_planner_hooks(before: Option<fftw_voidfunc>,
+                                   after: Option<fftw_voidfunc>);
+    pub fn fftwq_export_wisdom_to_filename(filename: *const c_char) -> c_int;
+    pub fn fftwq_export_wisdom_to_file(output_file: *mut FILE);
+    // the returned string must be freed with `fftwq_free`.
+    pub fn fftwq_export_wisdom_to_string() -> *mut c_char;
+    pub fn fftwq_export_wisdom(write_char: fftwq_write_char_func,
+                               data: *mut c_void);
+    pub fn fftwq_import_system_wisdom() -> c_int;
+    pub fn fftwq_import_wisdom_from_filename(filename: *const c_char) -> c_int;
+    pub fn fftwq_import_wisdom_from_file(input_file: *mut FILE) -> c_int;
+    pub fn fftwq_import_wisdom_from_string(input_string: *const c_char) -> c_int;
+    pub fn fftwq_import_wisdom(read_char: fftwq_read_char_func,
+                               data: *mut c_void) -> c_int;
+    pub fn fftwq_fprint_plan(p: fftwq_plan, output_file: *mut FILE);
+    pub fn fftwq_print_plan(p: fftwq_plan);
+    // the returned string must be freed with `fftwq_free`.
+    pub fn fftwq_sprint_plan(p: fftwq_plan) -> *mut c_char;
+    pub fn fftwq_malloc(n: size_t) -> *mut c_void;
+    pub fn fftwq_alloc_real(n: size_t) -> *mut __float128;
+    pub fn fftwq_alloc_complex(n: size_t) -> *mut fftwq_complex;
+    pub fn fftwq_free(p: *mut c_void);
+    pub fn fftwq_flops(p: fftwq_plan, add: *mut c_double, mul: *mut c_double,
+                       fmas: *mut c_double);
+    pub fn fftwq_estimate_cost(p: fftwq_plan) -> c_double;
+    pub fn fftwq_cost(p: fftwq_plan) -> c_double;
+    pub fn fftwq_alignment_of(p: *mut __float128) -> c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use libc::{c_int, c_double, c_float, ptrdiff_t};
+    use super::*;
+
+    #[test]
+    fn iodim_layout() {
+        let d = fftw_iodim { n: 1, is: 2, os: 3 };
+        assert_eq!(mem::size_of::<fftw_iodim>(), 3 * mem::size_of::<c_int>());
+        unsafe {
+            assert_eq!(mem::transmute::<_, [c_int; 3]>(d), [1, 2, 3]);
+        }
+
+        let d = fftw_iodim64 { n: 1, is: 2, os: 3 };
+        assert_eq!(mem::size_of::<fftw_iodim64>(), 3 * mem::size_of::<ptrdiff_t>());
+        unsafe {
+            assert_eq!(mem::transmute::<_, [ptrdiff_t; 3]>(d), [1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn complex_layout() {
+        assert_eq!(mem::size_of::<fftw_complex>(), 2 * mem::size_of::<c_double>());
+        assert_eq!(mem::align_of::<fftw_complex>(), mem::align_of::<c_double>());
+        assert_eq!(mem::size_of::<fftwf_complex>(), 2 * mem::size_of::<c_float>());
+        assert_eq!(mem::align_of::<fftwf_complex>(), mem::align_of::<c_float>());
+    }
+
+    #[test]
+    fn r2r_kinds() {
+        // the values of `enum fftw_r2r_kind_do_not_use_me` in fftw3.h
+        let kinds = [FFTW_R2HC, FFTW_HC2R, FFTW_DHT,
+                     FFTW_REDFT00, FFTW_REDFT01, FFTW_REDFT10, FFTW_REDFT11,
+                     FFTW_RODFT00, FFTW_RODFT01, FFTW_RODFT10, FFTW_RODFT11];
+        for (i, k) in kinds.iter().enumerate() {
+            assert_eq!(*k, i as fftw_r2r_kind);
+        }
+        assert_eq!(mem::size_of::<fftw_r2r_kind>(), mem::size_of::<c_int>());
+    }
 }
@@ -0,0 +1,354 @@
+//! Executing one plan on many buffers in parallel.
+//!
+//! For many small independent transforms, running a single-threaded
+//! plan on each buffer from several threads scales better than
+//! FFTW's own threading. An `Executor` does this with a
+//! `SharedPlan`, either over a collection of buffers or over one
+//! large buffer split into consecutive chunks.
+//!
+//! Each buffer (or chunk) is transformed into the output at the same
+//! position, so the results don't depend on how the work was
+//! scheduled, and if any fail the error reported is the one for the
+//! first.
+
+use std::{cmp, mem, slice, thread};
+use std::ops::DerefMut;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use scoped_threadpool::Pool;
+
+use builder2::{FftData, SharedPlan};
+use error::{Error, PlanResult};
+
+/// How an `Executor` runs transforms in parallel.
+enum Backend {
+    // `None` for a single thread, which runs batches itself.
+    Threads(usize, Option<Mutex<Pool>>),
+    #[cfg(feature = "rayon")]
+    Rayon,
+}
+
+/// Runs a plan over batches of buffers.
+///
+/// An executor with its own threads runs one batch at a time: batches
+/// submitted concurrently from several threads wait for each other.
+pub struct Executor {
+    backend: Backend,
+}
+
+impl Executor {
+    /// Split each batch between `threads` threads (at least one),
+    /// which are started now and kept until the executor is dropped.
+    pub fn new(threads: usize) -> Executor {
+        let threads = cmp::max(threads, 1);
+        let pool = if threads == 1 { None } else { Some(Mutex::new(Pool::new(threads as u32))) };
+        Executor { backend: Backend::Threads(threads, pool) }
+    }
+
+    /// The number of threads batches are split between, or `None`
+    /// for rayon's pool.
+    pub fn threads(&self) -> Option<usize> {
+        match self.backend {
+            Backend::Threads(threads, _) => Some(threads),
+            #[cfg(feature = "rayon")]
+            Backend::Rayon => None,
+        }
+    }
+
+    /// Run batches on rayon's global thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn rayon() -> Executor {
+        Executor { backend: Backend::Rayon }
+    }
+
+    /// Transform each of `inputs` into the output at the same
+    /// position.
+    pub fn execute<T, U, I, O>(&self, plan: &SharedPlan,
+                               inputs: &mut [I], outputs: &mut [O]) -> PlanResult<()>
+        where T: FftData<U>, I: DerefMut<Target = [T]> + Send, O: DerefMut<Target = [U]> + Send
+    {
+        if inputs.len() != outputs.len() {
+            return Err(Error::InvalidArgument("batch: different numbers of inputs and outputs"))
+        }
+        let (ins, outs) = (Items(inputs.as_mut_ptr()), Items(outputs.as_mut_ptr()));
+        self.run(inputs.len(), &|i| unsafe {
+            plan.execute(&mut **ins.get(i), &mut **outs.get(i))
+        })
+    }
+
+    /// Transform each of `bufs` in place.
+    pub fn execute_inplace<T, B>(&self, plan: &SharedPlan, bufs: &mut [B]) -> PlanResult<()>
+        where T: FftData<T>, B: DerefMut<Target = [T]> + Send
+    {
+        let items = Items(bufs.as_mut_ptr());
+        self.run(bufs.len(), &|i| unsafe { plan.execute_inplace(&mut **items.get(i)) })
+    }
+
+    /// Split `in_` and `out` into consecutive chunks of the lengths
+    /// the plan needs, and transform each input chunk into the
+    /// corresponding output one.
+    ///
+    /// Every chunk needs the SIMD alignment the plan was created
+    /// with, so the chunk sizes in bytes should be multiples of
+    /// `mem::simd_alignment()`.
+    pub fn execute_chunks<T, U>(&self, plan: &SharedPlan,
+                                in_: &mut [T], out: &mut [U]) -> PlanResult<()>
+        where T: FftData<U> + Send, U: Send
+    {
+        let (in_len, out_len) = plan.lens();
+        let n = try!(chunks(in_.len(), in_len));
+        if try!(chunks(out.len(), out_len)) != n {
+            return Err(Error::InvalidArgument("batch: different numbers of input and output \
+                                               chunks"))
+        }
+        let (ins, outs) = (Items(in_.as_mut_ptr()), Items(out.as_mut_ptr()));
+        self.run(n, &|i| unsafe {
+            plan.execute(slice::from_raw_parts_mut(ins.get(i * in_len), in_len),
+                         slice::from_raw_parts_mut(outs.get(i * out_len), out_len))
+        })
+    }
+
+    /// Split `buf` into consecutive chunks of the length the plan
+    /// needs, and transform each in place.
+    pub fn execute_inplace_chunks<T>(&self, plan: &SharedPlan, buf: &mut [T]) -> PlanResult<()>
+        where T: FftData<T> + Send
+    {
+        let len = plan.lens().0;
+        let n = try!(chunks(buf.len(), len));
+        let bufs = Items(buf.as_mut_ptr());
+        self.run(n, &|i| unsafe {
+            plan.execute_inplace(slice::from_raw_parts_mut(bufs.get(i * len), len))
+        })
+    }
+
+    /// Call `f` on every index below `n`, returning the error of the
+    /// smallest that fails.
+    fn run(&self, n: usize, f: &(Fn(usize) -> PlanResult<()> + Sync)) -> PlanResult<()> {
+        let result = match self.backend {
+            Backend::Threads(_, None) => run_range(0, n, f),
+            Backend::Threads(threads, Some(ref pool)) => run_pool(pool, threads, n, f),
+            #[cfg(feature = "rayon")]
+            Backend::Rayon => run_rayon(0, n, f),
+        };
+        result.map_err(|(_, e)| e)
+    }
+}
+
+/// The number of chunks of `len` elements in `total`.
+fn chunks(total: usize, len: usize) -> PlanResult<usize> {
+    if len == 0 || total % len != 0 {
+        Err(Error::InvalidArgument("batch: buffer isn't a whole number of chunks"))
+    } else {
+        Ok(total / len)
+    }
+}
+
+/// The items of a slice, shared between threads that each access
+/// different ones.
+struct Items<T>(*mut T);
+unsafe impl<T: Send> Send for Items<T> {}
+unsafe impl<T: Send> Sync for Items<T> {}
+
+impl<T> Items<T> {
+    unsafe fn get(&self, i: usize) -> *mut T {
+        self.0.offset(i as isize)
+    }
+}
+
+type BatchResult = Result<(), (usize, Error)>;
+
+/// The error of the earlier failure.
+fn earliest(a: BatchResult, b: BatchResult) -> BatchResult {
+    match (a, b) {
+        (Err(a), Err(b)) => Err(if a.0 <= b.0 { a } else { b }),
+        (Err(e), _) | (_, Err(e)) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn run_range(lo: usize, hi: usize, f: &(Fn(usize) -> PlanResult<()> + Sync)) -> BatchResult {
+    for i in lo..hi {
+        try!(f(i).map_err(|e| (i, e)))
+    }
+    Ok(())
+}
+
+/// Split the indices below `n` into a range for each of `threads`
+/// jobs on `pool`.
+fn run_pool(pool: &Mutex<Pool>, threads: usize, n: usize,
+            f: &(Fn(usize) -> PlanResult<()> + Sync)) -> BatchResult {
+    let threads = cmp::min(threads, n);
+    if threads <= 1 {
+        return run_range(0, n, f)
+    }
+    let result = Mutex::new(Ok(()));
+    let panicked = AtomicBool::new(false);
+    {
+        let (result, panicked) = (&result, &panicked);
+        // a job that panics would take its thread in the pool down
+        // with it, so the panic is caught in the job and raised again
+        // once the scope (which waits for every job) is done.
+        pool.lock().unwrap_or_else(|e| e.into_inner()).scoped(|scope| {
+            for t in 0..threads {
+                let (lo, hi) = (t * n / threads, (t + 1) * n / threads);
+                scope.execute(move || {
+                    match catch_range(lo, hi, f) {
+                        Some(r) => {
+                            let mut result = result.lock().unwrap_or_else(|e| e.into_inner());
+                            *result = earliest(mem::replace(&mut *result, Ok(())), r);
+                        }
+                        None => panicked.store(true, Ordering::SeqCst),
+                    }
+                });
+            }
+        });
+    }
+    if panicked.load(Ordering::SeqCst) {
+        panic!("batch: a transform panicked")
+    }
+    result.into_inner().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Run `f` on the indices from `lo` to `hi`, or return `None` if it
+/// panics.
+fn catch_range(lo: usize, hi: usize,
+               f: &(Fn(usize) -> PlanResult<()> + Sync)) -> Option<BatchResult> {
+    // `catch_panic` returns only once the closure has finished, so
+    // `f` needn't outlive this call.
+    let f: &'static (Fn(usize) -> PlanResult<()> + Sync) = unsafe {mem::transmute(f)};
+    thread::catch_panic(move || run_range(lo, hi, f)).ok()
+}
+
+#[cfg(feature = "rayon")]
+fn run_rayon(lo: usize, hi: usize, f: &(Fn(usize) -> PlanResult<()> + Sync)) -> BatchResult {
+    if hi - lo <= 1 {
+        return run_range(lo, hi, f)
+    }
+    let mid = lo + (hi - lo) / 2;
+    let (a, b) = ::rayon::join(|| run_rayon(lo, mid, f), || run_rayon(mid, hi, f));
+    earliest(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use num::complex::Complex64;
+    use rand::random;
+    use mem::FftwVec;
+    use builder2::{Planner, SharedPlan};
+    use error::{Buffer, Error};
+    use super::Executor;
+
+    fn c2c(n: usize) -> SharedPlan {
+        Planner::new().input(FftwVec::<Complex64>::zeros(n)).output(FftwVec::<Complex64>::zeros(n))
+            .plan().unwrap().into_shared()
+    }
+    fn c2c_inplace(n: usize) -> SharedPlan {
+        Planner::new().input(FftwVec::<Complex64>::zeros(n)).inplace()
+            .plan().unwrap().into_shared()
+    }
+
+    fn executors() -> Vec<Executor> {
+        let mut v = vec![Executor::new(1), Executor::new(3), Executor::new(64)];
+        v.extend(rayon());
+        return v;
+
+        #[cfg(feature = "rayon")]
+        fn rayon() -> Option<Executor> { Some(Executor::rayon()) }
+        #[cfg(not(feature = "rayon"))]
+        fn rayon() -> Option<Executor> { None }
+    }
+
+    fn close(a: &[Complex64], b: &[Complex64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| (*x - *y).norm() < 1e-10)
+    }
+
+    #[test]
+    fn matches_sequential() {
+        let plan = c2c(16);
+        let inputs = (0..37).map(|_| {
+            (0..16).map(|_| Complex64::new(random(), random())).collect::<FftwVec<_>>()
+        }).collect::<Vec<_>>();
+        let expected = inputs.iter().map(|x| {
+            let mut in_ = x.clone();
+            let mut out = FftwVec::<Complex64>::zeros(16);
+            plan.execute(&mut in_, &mut out).unwrap();
+            out
+        }).collect::<Vec<_>>();
+
+        for e in executors() {
+            let mut ins = inputs.clone();
+            let mut outs = (0..37).map(|_| FftwVec::<Complex64>::zeros(16)).collect::<Vec<_>>();
+            e.execute(&plan, &mut ins, &mut outs).unwrap();
+            // the same plan gives exactly the same results.
+            assert_eq!(outs, expected);
+
+            e.execute_inplace(&c2c_inplace(16), &mut ins).unwrap();
+            assert!(ins.iter().zip(expected.iter()).all(|(a, b)| close(a, b)));
+
+            // one big buffer.
+            let mut in_ = inputs.iter().flat_map(|x| x.iter().cloned()).collect::<FftwVec<_>>();
+            let mut out = FftwVec::<Complex64>::zeros(16 * 37);
+            e.execute_chunks(&plan, &mut in_, &mut out).unwrap();
+            for (chunk, x) in out.chunks(16).zip(expected.iter()) {
+                assert_eq!(chunk, &x[..]);
+            }
+            e.execute_inplace_chunks(&c2c_inplace(16), &mut in_).unwrap();
+            assert!(close(&in_, &out));
+        }
+    }
+
+    #[test]
+    fn first_error_is_reported() {
+        let plan = c2c(8);
+        for e in executors() {
+            let mut ins = (0..10).map(|i| FftwVec::<Complex64>::zeros(if i == 7 { 6 } else { 8 }))
+                .collect::<Vec<_>>();
+            let mut outs = (0..10).map(|i| FftwVec::<Complex64>::zeros(if i < 3 { 8 } else { 4 }))
+                .collect::<Vec<_>>();
+            assert_eq!(e.execute(&plan, &mut ins, &mut outs).err(),
+                       Some(Error::BufferTooSmall { buffer: Buffer::Output, required: 8,
+                                                    actual: 4 }));
+            assert!(e.execute(&plan, &mut ins[..3], &mut outs[..2]).is_err());
+
+            let mut in_ = FftwVec::<Complex64>::zeros(20);
+            let mut out = FftwVec::<Complex64>::zeros(24);
+            assert!(e.execute_chunks(&plan, &mut in_, &mut out).is_err());
+        }
+    }
+
+    #[test]
+    fn threads_are_reused() {
+        let e = Executor::new(4);
+        assert_eq!(e.threads(), Some(4));
+        assert_eq!(Executor::new(0).threads(), Some(1));
+
+        let plan = c2c_inplace(8);
+        let mut bufs = (0..9).map(|_| FftwVec::<Complex64>::zeros(8)).collect::<Vec<_>>();
+        for _ in 0..100 {
+            bufs[5][0] = Complex64::new(1.0, 0.0);
+            e.execute_inplace(&plan, &mut bufs).unwrap();
+            assert!(bufs[5].iter().all(|x| (*x - Complex64::new(1.0, 0.0)).norm() < 1e-10));
+            bufs[5] = FftwVec::zeros(8);
+        }
+    }
+
+    #[test]
+    fn survives_panicking_jobs() {
+        let e = Arc::new(Executor::new(3));
+        let e2 = e.clone();
+        let res = thread::spawn(move || {
+            e2.run(8, &|i| if i == 5 { panic!("job {}", i) } else { Ok(()) })
+        }).join();
+        assert!(res.is_err());
+
+        // every thread of the pool is still there to run later jobs.
+        for _ in 0..10 {
+            assert_eq!(e.run(8, &|_| Ok(())), Ok(()));
+        }
+        let plan = c2c_inplace(8);
+        let mut bufs = (0..9).map(|_| FftwVec::<Complex64>::zeros(8)).collect::<Vec<_>>();
+        assert!(e.execute_inplace(&plan, &mut bufs).is_ok());
+    }
+}
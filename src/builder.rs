@@ -1,48 +1,46 @@
-use ffi;
-use libc::{c_uint, c_int, c_void};
+//! The original planning interface, kept for compatibility.
+//!
+//! This is a thin layer over `builder2::Planner`, which supports
+//! everything this does and more (strided and multidimensional
+//! layouts, real-to-real transforms, ...), and should be preferred in
+//! new code.
+
+use libc::c_uint;
 use num::complex::Complex64;
 use std::ops::DerefMut;
 
-use plan::RawPlan;
+use builder2::{self, BufferRequirements, Begin, FftData, Inplace, Io, Kind, Ready};
+use error::{self, Error, PlanResult};
 
-/// How much effort FFTW should put into computing the best strategy
-/// to use.
+pub use builder2::{Rigor, Direction};
+
+/// A dimension of a transform: its length, and the distance between
+/// consecutive elements of the input and output.
 ///
-/// The `FFTW_WISDOM_ONLY` rigor level is replaced by the
-#[derive(Clone, Copy)]
-pub enum Rigor {
-    Estimate,
-    Measure,
-    Patient,
-    Exhaustive,
-}
-impl Rigor {
-    fn flags(self) -> c_uint {
-        match self {
-            Rigor::Estimate => ffi::FFTW_ESTIMATE,
-            Rigor::Measure => ffi::FFTW_MEASURE,
-            Rigor::Patient => ffi::FFTW_PATIENT,
-            Rigor::Exhaustive => ffi::FFTW_EXHAUSTIVE,
-        }
-    }
+/// Unlike `builder2::Dim`, the strides can't be negative.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Dim {
+    pub n: usize,
+    pub in_stride: usize,
+    pub out_stride: usize,
 }
 
-/// The direction of the transform to perform..
-#[derive(Clone, Copy)]
-pub enum Direction {
-    Forward, Backward
+impl Dim {
+    /// The same dimension for `builder2`, failing if a stride is too
+    /// large for its signed strides.
+    fn to_builder2(self) -> PlanResult<builder2::Dim> {
+        try!(error::check_isize(self.in_stride));
+        try!(error::check_isize(self.out_stride));
+        Ok(builder2::Dim { n: self.n, in_stride: self.in_stride as isize,
+                           out_stride: self.out_stride as isize })
+    }
 }
 
 /// Control the basic properties of a set of transforms.
 pub struct Planner {
-    rigor: Rigor,
-    wisdom_restriction: bool,
-    direction: Direction,
-
-    #[allow(dead_code)]
-    dims: Vec<Dim>,
-    #[allow(dead_code)]
-    howmany: Vec<Dim>,
+    inner: builder2::Planner<Begin, Begin>,
+    error: Option<Error>,
 }
 
 impl Planner {
@@ -50,291 +48,212 @@ impl Planner {
     ///
     /// This defaults to a forward transform with estimate rigor.
     pub fn new() -> Planner {
-        Planner {
-            rigor: Rigor::Estimate,
-            wisdom_restriction: false,
-            direction: Direction::Forward,
-            dims: vec![],
-            howmany: vec![],
-        }
+        Planner { inner: builder2::Planner::new(), error: None }
     }
 
     /// Set the rigor to use for this plan.
-    pub fn rigor(mut self, r: Rigor) -> Planner {
-        self.rigor = r;
-        self
+    pub fn rigor(self, r: Rigor) -> Planner {
+        Planner { inner: self.inner.rigor(r), error: self.error }
     }
     /// Set whether the planner should only be successfully created if
     /// there exists wisdom created with at least the rigor level set.
-    pub fn wisdom_restriction(mut self, wisdom_only: bool) -> Planner {
-        self.wisdom_restriction = wisdom_only;
-        self
+    pub fn wisdom_restriction(self, wisdom_only: bool) -> Planner {
+        Planner { inner: self.inner.wisdom_restriction(wisdom_only), error: self.error }
     }
 
     /// Set the direction of the transform to perform.
-    pub fn direction(mut self, direction: Direction) -> Planner {
-        self.direction = direction;
-        self
-    }
-
-    #[cfg(higher_dim_is_hard)]
-    pub fn dims_row_major(mut self, dims: Vec<uint>) -> Planner {
-        assert!(dims.len() >= 1);
-
-        self.dims = dims.iter().map(|n| Dim { n: *n, in_stride: 1, out_stride: 1 });
-        for i in range(0, self.dims.len() - 1).rev() {
-            let stride = self.dims[i + 1].n * self.dims[i + 1].in_stride;
-            self.dims[i].in_stride = stride;
-            self.dims[i].out_stride = stride;
-        }
-
-        self
-    }
-    #[cfg(higher_dim_is_hard)]
-    pub fn multiples(mut self, number: uint) -> PlanMem<I, O> {
-        unimplemented!();
-        self.how_many = Contiguous(vec![number]);
-        self
+    ///
+    /// Real-to-complex transforms are always forward, and
+    /// complex-to-real ones always backward, so setting the other
+    /// direction for these is an error.
+    pub fn direction(self, direction: Direction) -> Planner {
+        Planner { inner: self.inner.direction(direction), error: self.error }
     }
-
-    fn flags(&self) -> c_uint {
-        self.rigor.flags() | if self.wisdom_restriction {
-            ffi::FFTW_WISDOM_ONLY
-        } else {
-            0
-        }
+    /// Set extra FFTW flags, e.g. `FFTW_DESTROY_INPUT`.
+    pub fn flags(self, flags: c_uint) -> Planner {
+        Planner { inner: self.inner.flags(flags), error: self.error }
     }
-    fn dir(&self) -> c_int {
-        match self.direction {
-            Direction::Forward => ffi::FFTW_FORWARD,
-            Direction::Backward => ffi::FFTW_BACKWARD,
+    /// Repeat the transform over the given dimensions, with strides
+    /// giving the distance between each transform.
+    pub fn howmany(self, dims: &[Dim]) -> Planner {
+        match dims.iter().map(|d| d.to_builder2()).collect::<PlanResult<Vec<_>>>() {
+            Ok(dims) => Planner { inner: self.inner.howmany(&dims), error: self.error },
+            Err(e) => Planner { inner: self.inner, error: self.error.or(Some(e)) },
         }
     }
 
     pub fn inplace(self) -> InPlacePlanner {
-        InPlacePlanner { plan: self }
+        InPlacePlanner { inner: self.inner, error: self.error }
+    }
+
+    /// The buffers required by a `kind` transform of logical length
+    /// `n` (the length of the real array, for real-to-complex and
+    /// complex-to-real transforms), repeated as set by `howmany`.
+    pub fn buffer_requirements(&self, kind: Kind, n: usize) -> PlanResult<BufferRequirements> {
+        requirements(&self.inner, &self.error, kind, n, false)
     }
 
     pub fn c2c<I, O>(self, in_: I, out: O) -> PlanMem<I, O>
         where I: DerefMut<Target = [Complex64]>, O: DerefMut<Target = [Complex64]>
     {
-        assert!(in_.len() <= 0x7F_FF_FF_FF);
-        assert!(in_.len() <= out.len());
-        let dims = vec![Dim { n: in_.len(), in_stride: 1, out_stride: 1 }];
-        PlanMem {
-            plan: self,
-            in_: in_,
-            out: Some(out),
-            planner: c2c,
-
-            dims: dims,
-            how_many: vec![],
-        }
+        let n = in_.len();
+        let stage = Stage::Io(self.inner.input(in_).output(out)._1d(n));
+        PlanMem { error: self.error, stage: stage }
     }
-    pub fn c2r<I, O>(self, in_: I, out: O) -> PlanMem<I, O>
+    /// Plan a complex-to-real transform whose real output has length
+    /// `n`, so `in_` needs at least `n / 2 + 1` elements and `out` at
+    /// least `n`.
+    ///
+    /// The length can't be inferred from the input, since both `2 * k`
+    /// and `2 * k + 1` real elements have `k + 1` complex ones.
+    pub fn c2r<I, O>(self, n: usize, in_: I, out: O) -> PlanMem<I, O>
         where I: DerefMut<Target = [Complex64]>, O: DerefMut<Target = [f64]>
     {
-        assert!(in_.len() <= 0x7F_FF_FF_FF);
-        assert!(in_.len() <= out.len() / 2 + 1);
-        let dims = vec![Dim { n: 2 * (in_.len() - 1), in_stride: 1, out_stride: 1 }];
-        PlanMem {
-            plan: self,
-            in_: in_,
-            out: Some(out),
-            planner: c2r,
-
-            dims: dims,
-            how_many: vec![],
-        }
+        let error = if n == 0 { Some(Error::InvalidArgument("c2r: zero length")) } else { None };
+        let error = self.error.or(error);
+        let stage = Stage::Io(self.inner.input(in_).output(out)._1d(n));
+        PlanMem { error: error, stage: stage }
     }
     pub fn r2c<I, O>(self, in_: I, out: O) -> PlanMem<I, O>
         where I: DerefMut<Target = [f64]>, O: DerefMut<Target = [Complex64]>
     {
-        assert!(in_.len() <= 0x7F_FF_FF_FF);
-        assert!(in_.len() / 2 + 1 <= out.len());
-        let dims = vec![Dim { n: in_.len(), in_stride: 1, out_stride: 1 }];
-        PlanMem {
-            plan: self,
-            in_: in_,
-            out: Some(out),
-            planner: r2c,
-
-            dims: dims,
-            how_many: vec![],
-        }
-    }
-    #[cfg(r2r_is_hard)]
-    pub fn r2r<I, O>(self, in_: I, out: O) -> PlanMem<I, O>
-        where I: DerefMut<[f64]>, O: DerefMut<[f64]>
-    {
-        assert!(in_.len() <= 0x7F_FF_FF_FF);
-        assert!(in_.len() <= out.len());
-        let dims = vec![Dim { n: in_.len(), in_stride: 1, out_stride: 1 }];
-        PlanMem {
-            plan: *self,
-            in_: in_,
-            out: Some(out),
-            planner: r2r,
-
-            dims: dims,
-            how_many: vec![],
-        }
+        let n = in_.len();
+        let stage = Stage::Io(self.inner.input(in_).output(out)._1d(n));
+        PlanMem { error: self.error, stage: stage }
     }
 }
 
 pub struct InPlacePlanner {
-    plan: Planner
+    inner: builder2::Planner<Begin, Begin>,
+    error: Option<Error>,
 }
 
 impl InPlacePlanner {
+    /// The buffer required by an in-place `kind` transform of length
+    /// `n`, repeated as set by `howmany`.
+    pub fn buffer_requirements(&self, kind: Kind, n: usize) -> PlanResult<BufferRequirements> {
+        requirements(&self.inner, &self.error, kind, n, true)
+    }
+
     pub fn c2c<I>(self, in_: I) -> PlanMem<I, I>
         where I: DerefMut<Target = [Complex64]>
     {
-        assert!(in_.len() <= 0x7F_FF_FF_FF);
-        let dims = vec![Dim { n: in_.len(), in_stride: 1, out_stride: 1 }];
-        PlanMem {
-            plan: self.plan,
-            in_: in_,
-            out: None,
-            planner: c2c,
-
-            dims: dims,
-            how_many: vec![],
-        }
+        let n = in_.len();
+        let stage = Stage::Inplace(self.inner.input(in_).inplace()._1d(n));
+        PlanMem { error: self.error, stage: stage }
     }
 }
 
-type GuruPlanner =
-    unsafe fn(rank: c_int, dims: *const ffi::fftw_iodim64,
-              howmany_rank: c_int, howmany_dims: *const ffi::fftw_iodim64,
-              in_: *mut c_void, out: *mut c_void,
-              sign: c_int, flags: c_uint) -> ffi::fftw_plan;
-
-unsafe fn c2c(rank: c_int, dims: *const ffi::fftw_iodim64,
-              howmany_rank: c_int, howmany_dims: *const ffi::fftw_iodim64,
-              in_: *mut c_void, out: *mut c_void,
-              sign: c_int, flags: c_uint) -> ffi::fftw_plan {
-    ffi::fftw_plan_guru64_dft(rank, dims,
-                            howmany_rank, howmany_dims,
-                            in_ as *mut _, out as *mut _,
-                            sign, flags)
+fn requirements(planner: &builder2::Planner<Begin, Begin>, error: &Option<Error>,
+                kind: Kind, n: usize, inplace: bool) -> PlanResult<BufferRequirements> {
+    if let Some(ref e) = *error {
+        return Err(e.clone())
+    }
+    if kind == Kind::R2r {
+        return Err(Error::Unsupported("real-to-real transforms with the old builder"))
+    }
+    try!(error::check_int(n));
+    let dims = [try!(Dim { n: n, in_stride: 1, out_stride: 1 }.to_builder2())];
+    builder2::buffer_requirements(kind, &dims, planner.howmany_dims(), inplace)
 }
 
-unsafe fn r2c(rank: c_int, dims: *const ffi::fftw_iodim64,
-              howmany_rank: c_int, howmany_dims: *const ffi::fftw_iodim64,
-              in_: *mut c_void, out: *mut c_void,
-              _sign: c_int, flags: c_uint) -> ffi::fftw_plan {
-    ffi::fftw_plan_guru64_dft_r2c(rank, dims,
-                                howmany_rank, howmany_dims,
-                                in_ as *mut _, out as *mut _,
-                                flags)
-}
-unsafe fn c2r(rank: c_int, dims: *const ffi::fftw_iodim64,
-              howmany_rank: c_int, howmany_dims: *const ffi::fftw_iodim64,
-              in_: *mut c_void, out: *mut c_void,
-              _sign: c_int, flags: c_uint) -> ffi::fftw_plan {
-    ffi::fftw_plan_guru64_dft_c2r(rank, dims,
-                                howmany_rank, howmany_dims,
-                                in_ as *mut _, out as *mut _,
-                                flags)
-}
-#[cfg(r2r_is_hard)]
-unsafe fn r2r(n: c_int, in_: *mut c_void, out: *mut c_void,
-              sign: c_int, flags: c_uint) -> ffi::fftw_plan {
-    ffi::fftw_plan_r2r_1d(n,
-                          in_ as *mut f64, out as *mut f64,
-                          sign, flags)
-}
-
-
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub struct Dim {
-    pub n: usize,
-    pub in_stride: usize,
-    pub out_stride: usize,
+enum Stage<I, O> {
+    Io(builder2::Planner<Io<I, O>, Ready>),
+    // only ever constructed with `I == O`.
+    Inplace(builder2::Planner<Inplace<I>, Ready>),
 }
 
 pub struct PlanMem<I, O> {
-    plan: Planner,
-    dims: Vec<Dim>,
-    #[allow(dead_code)]
-    how_many: Vec<Dim>,
-    in_: I,
-    out: Option<O>,
-    planner: GuruPlanner
+    error: Option<Error>,
+    stage: Stage<I, O>,
 }
 
-impl<X, Y, I: DerefMut<Target = [X]>, O: DerefMut<Target = [Y]>> PlanMem<I, O> {
-    pub fn plan(mut self) -> Result<Planned<I, O>, PlanMem<I, O>> {
-        let plan;
-        {
-            let in_ptr = self.in_.as_mut_ptr() as *mut c_void;
-            let out_ptr = match self.out {
-                None => in_ptr,
-                Some(ref mut o) => o.as_mut_ptr() as *mut c_void,
-            };
-            assert!(self.dims.len() == 1);
-
-            plan = RawPlan::new(|| unsafe {
-                (self.planner)(
-                    self.dims.len() as c_int,
-                    self.dims.as_ptr() as *const ffi::fftw_iodim64,
-                    0, [].as_ptr(),
-                    in_ptr,
-                    out_ptr,
-                    self.plan.dir(),
-                    self.plan.flags())
-            });
-        }
-        match plan {
-            None => Err(self),
-            Some(p) => Ok(Planned { mem: self, plan: p })
+// every element type the old builder handles can be transformed to
+// itself, which the in-place case needs.
+impl<X, Y, I, O> PlanMem<I, O>
+    where I: DerefMut<Target = [X]>, O: DerefMut<Target = [Y]>,
+          X: FftData<Y> + FftData<X>
+{
+    /// Create the plan, failing if the buffers given were unsuitable
+    /// or FFTW could not plan the transform.
+    pub fn plan(self) -> PlanResult<Planned<I, O>> {
+        if let Some(e) = self.error {
+            return Err(e)
         }
+        let plan = match self.stage {
+            Stage::Io(p) => PlannedStage::Io(try!(p.plan())),
+            Stage::Inplace(p) => PlannedStage::Inplace(try!(p.plan())),
+        };
+        Ok(Planned { plan: plan })
     }
 }
 
+enum PlannedStage<I, O> {
+    Io(builder2::Plan<Io<I, O>>),
+    Inplace(builder2::Plan<Inplace<I>>),
+}
+
 pub struct Planned<I, O> {
-    mem: PlanMem<I, O>,
-    plan: RawPlan,
+    plan: PlannedStage<I, O>,
 }
 
 impl<I: DerefMut, O: DerefMut> Planned<I, O> {
     pub fn input(&mut self) -> &mut I::Target {
-        &mut *self.mem.in_
+        match self.plan {
+            PlannedStage::Io(ref mut p) => &mut **p.input(),
+            PlannedStage::Inplace(ref mut p) => &mut **p.in_out(),
+        }
     }
+    /// The output, or `None` for an in-place transform.
     pub fn output(&mut self) -> Option<&mut O::Target> {
-        self.mem.out.as_mut().map(|o| &mut **o)
+        match self.plan {
+            PlannedStage::Io(ref mut p) => Some(&mut **p.output()),
+            PlannedStage::Inplace(_) => None,
+        }
     }
 
     pub fn execute(&mut self) {
-        unsafe {
-            self.plan.execute()
+        match self.plan {
+            PlannedStage::Io(ref mut p) => p.execute(),
+            PlannedStage::Inplace(ref mut p) => p.execute(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::mem;
-    use libc::ptrdiff_t;
-    use ffi::Struct_fftw_iodim64_do_not_use_me;
-    use super::Dim;
+    use builder2::{self, Kind};
+    use error::Error;
+    use super::{Dim, Planner};
 
     #[test]
-    fn iodims_are_compatible() {
-        // handle 32-bit and 64-bit platforms properly
-        let n = 0x0102_0304_0506_0708u64 as usize;
-        let is = 0x090A_0B0C_0D0E_0F00u64 as usize;
-        let os = 0x1122_3344_5566_7788u64 as usize;
+    fn buffer_requirements() {
+        let req = Planner::new().buffer_requirements(Kind::R2c, 9).unwrap();
+        assert_eq!((req.input, req.output), (9, 5));
+        let req = Planner::new().inplace().buffer_requirements(Kind::C2c, 9).unwrap();
+        assert_eq!((req.input, req.output), (9, 9));
+        assert!(Planner::new().buffer_requirements(Kind::R2r, 9).is_err());
+
+        // three transforms, 16 elements apart.
+        let howmany = [Dim { n: 3, in_stride: 16, out_stride: 16 }];
+        let req = Planner::new().howmany(&howmany).buffer_requirements(Kind::C2c, 9).unwrap();
+        assert_eq!((req.input, req.output), (2 * 16 + 9, 2 * 16 + 9));
+        let req = Planner::new().howmany(&howmany).inplace()
+            .buffer_requirements(Kind::C2c, 9).unwrap();
+        assert_eq!((req.input, req.output), (2 * 16 + 9, 2 * 16 + 9));
+
+        let max = ::std::isize::MAX as usize;
+        let huge = [Dim { n: 3, in_stride: max + 1, out_stride: 1 }];
+        assert_eq!(Planner::new().howmany(&huge).buffer_requirements(Kind::C2c, 9).err(),
+                   Some(Error::SizeOverflow { size: max + 1, max: max }));
+    }
 
-        let d = Dim { n: n, in_stride: is, out_stride: os };
-        let f = Struct_fftw_iodim64_do_not_use_me {
-            n: n as ptrdiff_t, is: is as ptrdiff_t, os: os as ptrdiff_t
-        };
-        type T = (usize, usize, usize);
-        unsafe {
-            assert_eq!(mem::transmute::<_, T>(d), mem::transmute::<_, T>(f));
-        }
+    #[test]
+    fn dims_convert() {
+        let d = Dim { n: 4, in_stride: 2, out_stride: 3 };
+        assert_eq!(d.to_builder2(), Ok(builder2::Dim { n: 4, in_stride: 2, out_stride: 3 }));
+        let max = ::std::isize::MAX as usize;
+        assert_eq!(Dim { n: 4, in_stride: 1, out_stride: max + 1 }.to_builder2(),
+                   Err(Error::SizeOverflow { size: max + 1, max: max }));
     }
 }
@@ -0,0 +1,276 @@
+//! Planning transforms whose buffers are allocated by the planner.
+
+use std::slice;
+use std::marker::PhantomData;
+use strided::MutStride;
+
+use mem::FftwVec;
+use plan::RawPlan;
+use traits::Zero;
+use error::Error;
+use super::{Begin, Dim, FftData, FftSpec, Kind, Meta, Plan, Planner, PlanResult, Secret, View,
+            alignment_of, buffer_requirements};
+
+/// A transform from `T`s to `U`s of a declared shape, whose buffers
+/// are allocated when it is planned.
+pub struct Alloc<T, U> {
+    shape: Vec<usize>,
+    batch: usize,
+    inplace: bool,
+
+    // allocated when planning; `out` is `None` for in-place
+    // transforms, which use `in_` for both.
+    in_: Option<FftwVec<T>>,
+    out: Option<FftwVec<U>>,
+    in_len: usize,
+    out_len: usize,
+}
+
+impl Planner<Begin, Begin> {
+    /// Plan a transform from `T`s to `U`s with the given logical
+    /// shape (that of the real array, for real-to-complex and
+    /// complex-to-real transforms), allocating suitable buffers.
+    ///
+    /// The buffers are contiguous and row-major, with the complex side
+    /// of a real transform having `n / 2 + 1` elements along the last
+    /// axis.
+    pub fn allocate<T, U>(self, shape: &[usize]) -> Planner<Alloc<T, U>, <T as FftData<U>>::State>
+        where T: FftData<U>
+    {
+        let mut p = Planner {
+            meta: self.meta,
+            data: Alloc {
+                shape: shape.to_vec(),
+                batch: 1,
+                inplace: false,
+                in_: None,
+                out: None,
+                in_len: 0,
+                out_len: 0,
+            },
+            _marker: PhantomData
+        };
+        if shape.is_empty() {
+            p.meta.error = Some(Error::InvalidArgument("Planner.allocate: empty shape"));
+        }
+        p.layout();
+        p
+    }
+}
+
+impl<T: FftData<U>, U, Y> Planner<Alloc<T, U>, Y> {
+    /// Perform `n` transforms of the declared shape, stored one after
+    /// another.
+    pub fn batch(mut self, n: usize) -> Planner<Alloc<T, U>, Y> {
+        self.data.batch = n;
+        self.layout();
+        self
+    }
+
+    /// Transform in place, using a single buffer.
+    ///
+    /// For real-to-complex and complex-to-real transforms, the last
+    /// axis of the real array is padded to `2 * (n / 2 + 1)` elements
+    /// so that the complex array fits.
+    pub fn inplace(mut self) -> Planner<Alloc<T, U>, Y> {
+        self.data.inplace = true;
+        self.layout();
+        self
+    }
+
+    /// Set the dimensions for the declared shape and options.
+    fn layout(&mut self) {
+        let kind = <T as FftData<U>>::kind();
+        let shape = &self.data.shape;
+
+        let mut half = shape.clone();
+        let mut padded = shape.clone();
+        if let Some(n) = shape.last() {
+            let h = n / 2 + 1;
+            *half.last_mut().unwrap() = h;
+            if self.data.inplace {
+                *padded.last_mut().unwrap() = 2 * h;
+            }
+        }
+        let (in_shape, out_shape) = match kind {
+            Kind::R2c => (padded, half),
+            Kind::C2r => (half, padded),
+            Kind::C2c | Kind::R2r => (shape.clone(), shape.clone()),
+        };
+
+        // row-major strides, and the distance between each transform.
+        fn strides(shape: &[usize]) -> (Vec<isize>, isize) {
+            let mut strides = vec![0; shape.len()];
+            let mut stride = 1usize;
+            for (s, &n) in strides.iter_mut().zip(shape.iter()).rev() {
+                *s = stride as isize;
+                stride = stride.saturating_mul(n);
+            }
+            (strides, stride as isize)
+        }
+        let (in_strides, in_dist) = strides(&in_shape);
+        let (out_strides, out_dist) = strides(&out_shape);
+
+        self.meta.dims = shape.iter().zip(in_strides.iter().zip(out_strides.iter()))
+            .map(|(&n, (&i, &o))| Dim { n: n, in_stride: i, out_stride: o })
+            .collect();
+        self.meta.howmany = if self.data.batch == 1 {
+            vec![]
+        } else {
+            vec![Dim { n: self.data.batch, in_stride: in_dist, out_stride: out_dist }]
+        };
+    }
+}
+
+impl<T: FftData<U> + Zero, U: Zero> FftSpec for Alloc<T, U> {
+    type Input = T;
+    type Output = U;
+    #[doc(hidden)]
+    unsafe fn plan(&mut self, meta: &Meta) -> PlanResult<RawPlan> {
+        let req = try!(buffer_requirements(<T as FftData<U>>::kind(), &meta.dims,
+                                           &meta.howmany, self.inplace));
+        let (in_len, out_len) = (req.input, req.output);
+
+        let plan = if self.inplace {
+            let mut buf = FftwVec::<T>::zeros(in_len);
+
+            // both views are of the same memory, so FFTW sees that the
+            // transform is in place.
+            let ptr = buf.as_mut_ptr();
+            let in_ = View { ptr: ptr, len: in_len, stride: 1 };
+            let out = View { ptr: ptr as *mut U, len: out_len, stride: 1 };
+            let plan = FftData::plan(in_, Some(out), meta);
+            self.in_ = Some(buf);
+            plan
+        } else {
+            let mut in_ = FftwVec::<T>::zeros(in_len);
+            let mut out = FftwVec::<U>::zeros(out_len);
+            let plan = FftData::plan(View::new(MutStride::new(&mut in_)),
+                                     Some(View::new(MutStride::new(&mut out))), meta);
+            self.in_ = Some(in_);
+            self.out = Some(out);
+            plan
+        };
+        let plan = try!(plan);
+
+        // planning with more rigor than `Estimate` scribbles on the
+        // buffers.
+        for x in self.in_.as_mut().unwrap().iter_mut() {
+            *x = Zero::zero()
+        }
+        if let Some(ref mut out) = self.out {
+            for x in out.iter_mut() {
+                *x = Zero::zero()
+            }
+        }
+        self.in_len = in_len;
+        self.out_len = out_len;
+        Ok(plan)
+    }
+    #[doc(hidden)]
+    fn kind() -> Kind { <T as FftData<U>>::kind() }
+    #[doc(hidden)]
+    fn layout(&mut self) -> (usize, Option<usize>, Dim) {
+        // the dimensions are always set, so the default is never used.
+        let in_ = alignment_of(self.in_.as_ref().map_or(0 as *const T, |v| v.as_ptr()));
+        let out = self.out.as_ref().map(|v| alignment_of(v.as_ptr()));
+        (in_, out, Dim { n: self.in_len, in_stride: 1, out_stride: 1 })
+    }
+    #[doc(hidden)]
+    fn secret() -> Secret { Secret(()) }
+}
+
+impl<T, U> Plan<Alloc<T, U>> {
+    /// The input buffer, with the batch of transforms stored one after
+    /// another.
+    ///
+    /// For in-place transforms this is the same memory as the output.
+    pub fn input(&mut self) -> &mut [T] {
+        let data = &mut self.planner.data;
+        let len = data.in_len;
+        &mut data.in_.as_mut().unwrap()[..len]
+    }
+
+    /// The output buffer, with the batch of transforms stored one
+    /// after another.
+    ///
+    /// For in-place transforms this is the same memory as the input.
+    pub fn output(&mut self) -> &mut [U] {
+        let data = &mut self.planner.data;
+        let len = data.out_len;
+        match data.out {
+            Some(ref mut out) => &mut out[..len],
+            None => unsafe {
+                let ptr = data.in_.as_mut().unwrap().as_mut_ptr();
+                slice::from_raw_parts_mut(ptr as *mut U, len)
+            }
+        }
+    }
+
+    /// The logical shape of each transform.
+    pub fn shape(&self) -> &[usize] {
+        &self.planner.data.shape
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::complex::Complex64;
+    use rand::random;
+    use mem::FftwVec;
+    use super::super::{Planner, R2rKind};
+
+    #[test]
+    fn r2c_batch() {
+        let mut plan = Planner::new().allocate::<f64, Complex64>(&[4, 6]).batch(2).plan().unwrap();
+        assert_eq!(plan.input().len(), 48);
+        assert_eq!(plan.output().len(), 32);
+
+        for x in plan.input().iter_mut() {
+            *x = random();
+        }
+        let mut in_ = FftwVec::from_slice(&plan.input()[24..]);
+        let mut expected = FftwVec::<Complex64>::zeros(16);
+        Planner::new().input(&mut in_[..]).output(&mut expected[..])
+            ._2d(4, 6).plan().unwrap().execute();
+
+        plan.execute();
+        for (a, b) in plan.output()[16..].iter().zip(expected.iter()) {
+            assert!((*a - *b).norm() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn inplace_padding() {
+        let mut plan = Planner::new().allocate::<f64, Complex64>(&[3, 8]).inplace().plan().unwrap();
+        // each row of 8 reals is padded to 10.
+        assert_eq!(plan.input().len(), 30);
+        assert_eq!(plan.output().len(), 15);
+        plan.input()[0] = 1.0;
+        plan.execute();
+        assert!(plan.output().iter().all(|x| (*x - Complex64::new(1.0, 0.0)).norm() < 1e-10));
+
+        let mut plan = Planner::new().allocate::<Complex64, f64>(&[5]).inplace().plan().unwrap();
+        assert_eq!(plan.input().len(), 3);
+        assert_eq!(plan.output().len(), 6);
+        plan.input()[0] = Complex64::new(1.0, 0.0);
+        plan.execute();
+        assert!(plan.output()[..5].iter().all(|x| (*x - 1.0).abs() < 1e-10));
+    }
+
+    #[test]
+    fn c2c_and_r2r() {
+        let plan = Planner::new().allocate::<Complex64, Complex64>(&[4]).batch(3).inplace()
+            .plan().unwrap();
+        assert_eq!(plan.shape(), &[4]);
+        let mut plan = plan;
+        assert_eq!(plan.input().len(), 12);
+
+        let plan = Planner::new().allocate::<f64, f64>(&[4, 4]).r2r_kinds(&[R2rKind::Dct10])
+            .plan().unwrap();
+        drop(plan);
+
+        assert!(Planner::new().allocate::<f64, f64>(&[]).r2r_kinds(&[R2rKind::Dht])
+                .plan().is_err());
+    }
+}
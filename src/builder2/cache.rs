@@ -0,0 +1,432 @@
+//! Caching plans, so that transforms of the same shape are only
+//! planned once.
+//!
+//! Plans are looked up by a `Key` describing everything that affects
+//! them: the `Template` (dimensions, strides, rigor, flags, direction
+//! and real-to-real kinds), the kind of transform, and the alignment
+//! of the buffers. They are created with scratch buffers, so looking
+//! one up never touches the caller's data, and handed out as
+//! `CachedPlan`s to be executed on the caller's buffers from any
+//! thread.
+//!
+//! There is a global cache shared by every thread (`global`), and one
+//! for each thread (`with_local`), which avoids contention on the
+//! global one's lock. Plans keep FFTW's global state alive, so clear
+//! the caches before calling `context::cleanup`.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::{mem, slice};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+
+use mem::FftwVec;
+use traits::Zero;
+use error::Error;
+use super::{FftData, Kind, PlanResult, SharedPlan, Template, alignment_of, buffer_requirements};
+
+/// The floating point precision of a transform.
+///
+/// Only FFTW's double precision interface is bound at the moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Precision {
+    Double,
+}
+
+/// Everything that determines a cached plan.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub precision: Precision,
+    pub kind: Kind,
+    pub inplace: bool,
+    /// The SIMD alignment of the input buffer, as given by
+    /// `fftw_alignment_of`; buffers executed with the plan must have
+    /// the same.
+    pub in_alignment: usize,
+    /// The SIMD alignment of the output buffer (the same as the input
+    /// for in-place transforms).
+    pub out_alignment: usize,
+    pub template: Template,
+}
+
+/// A plan from a cache, which can be executed on any buffers with the
+/// layout and alignment it was planned for, from any thread.
+///
+/// This dereferences to the `SharedPlan` that does the work, so it
+/// can be executed directly or given to a `batch::Executor`.
+pub struct CachedPlan {
+    plan: SharedPlan,
+    key: Key,
+}
+
+impl CachedPlan {
+    /// What this plan was created for.
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+impl Deref for CachedPlan {
+    type Target = SharedPlan;
+    fn deref(&self) -> &SharedPlan {
+        &self.plan
+    }
+}
+
+/// When to forget plans.
+///
+/// The least recently used plans are evicted first. Plans that are
+/// still in use stay alive until they are dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    /// Keep at most this many plans.
+    Count(usize),
+    /// Keep plans for transforms whose buffers total at most this
+    /// many bytes (a proxy for the memory of the plans themselves,
+    /// which FFTW doesn't report).
+    Memory(usize),
+}
+
+/// The eviction policy of the global and thread-local caches.
+pub const DEFAULT_EVICTION: Eviction = Eviction::Count(32);
+
+struct Entry {
+    plan: Arc<CachedPlan>,
+    bytes: usize,
+    last_used: u64,
+}
+
+struct Inner {
+    plans: HashMap<Key, Entry>,
+    eviction: Eviction,
+    clock: u64,
+    bytes: usize,
+}
+
+impl Inner {
+    fn evict(&mut self) {
+        loop {
+            let over = match self.eviction {
+                Eviction::Count(n) => self.plans.len() > n,
+                Eviction::Memory(n) => self.bytes > n,
+            };
+            if !over {
+                return
+            }
+            // caches are small, so a linear scan for the oldest is
+            // fine.
+            let oldest = match self.plans.iter().fold(None, |best: Option<(&Key, u64)>, (k, e)| {
+                match best {
+                    Some((_, t)) if t <= e.last_used => best,
+                    _ => Some((k, e.last_used)),
+                }
+            }) {
+                Some((k, _)) => k.clone(),
+                None => return
+            };
+            let e = self.plans.remove(&oldest).unwrap();
+            self.bytes -= e.bytes;
+        }
+    }
+}
+
+/// A thread-safe cache of plans.
+pub struct PlanCache {
+    inner: Mutex<Inner>
+}
+
+impl PlanCache {
+    /// Create an empty cache.
+    pub fn new(eviction: Eviction) -> PlanCache {
+        PlanCache {
+            inner: Mutex::new(Inner {
+                plans: HashMap::new(),
+                eviction: eviction,
+                clock: 0,
+                bytes: 0,
+            })
+        }
+    }
+
+    /// Change when plans are evicted, evicting any that no longer
+    /// fit.
+    pub fn set_eviction(&self, eviction: Eviction) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.eviction = eviction;
+        inner.evict()
+    }
+
+    /// The number of plans in the cache.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().plans.len()
+    }
+
+    /// The total size in bytes of the buffers of the cached plans.
+    pub fn memory(&self) -> usize {
+        self.inner.lock().unwrap().bytes
+    }
+
+    /// Forget every plan.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.plans.clear();
+        inner.bytes = 0;
+    }
+
+    /// Find or create a plan transforming buffers like `in_` into
+    /// buffers like `out` (which are only used for their alignment).
+    ///
+    /// The template must have its dimensions set.
+    pub fn get<T, U>(&self, template: &Template, in_: &[T], out: &[U])
+                     -> PlanResult<Arc<CachedPlan>>
+        where T: FftData<U> + Zero, U: Zero
+    {
+        let key = Key {
+            precision: Precision::Double,
+            kind: <T as FftData<U>>::kind(),
+            inplace: false,
+            in_alignment: alignment_of(in_.as_ptr()),
+            out_alignment: alignment_of(out.as_ptr()),
+            template: template.clone(),
+        };
+        self.lookup_or_plan(key, |key| {
+            let req = try!(buffer_requirements(key.kind, key.template.dims(),
+                                               key.template.howmany_dims(), false));
+            let mut in_buf = try!(Scratch::<T>::new(req.input, key.in_alignment));
+            let mut out_buf = try!(Scratch::<U>::new(req.output, key.out_alignment));
+            let plan = try!(key.template.plan_for(in_buf.as_mut_slice(),
+                                                  out_buf.as_mut_slice()));
+            let bytes = req.input * mem::size_of::<T>() + req.output * mem::size_of::<U>();
+            Ok((plan.into_shared(), bytes))
+        })
+    }
+
+    /// Find or create a plan transforming buffers like `buf` in
+    /// place.
+    pub fn get_inplace<T>(&self, template: &Template, buf: &[T]) -> PlanResult<Arc<CachedPlan>>
+        where T: FftData<T> + Zero
+    {
+        let alignment = alignment_of(buf.as_ptr());
+        let key = Key {
+            precision: Precision::Double,
+            kind: <T as FftData<T>>::kind(),
+            inplace: true,
+            in_alignment: alignment,
+            out_alignment: alignment,
+            template: template.clone(),
+        };
+        self.lookup_or_plan(key, |key| {
+            let req = try!(buffer_requirements(key.kind, key.template.dims(),
+                                               key.template.howmany_dims(), true));
+            let mut buf = try!(Scratch::<T>::new(req.input, key.in_alignment));
+            let plan = try!(key.template.plan_inplace_for(buf.as_mut_slice()));
+            Ok((plan.into_shared(), req.input * mem::size_of::<T>()))
+        })
+    }
+
+    /// Transform `in_` into `out` with a cached plan.
+    pub fn execute<T, U>(&self, template: &Template, in_: &mut [T], out: &mut [U]) -> PlanResult<()>
+        where T: FftData<U> + Zero, U: Zero
+    {
+        let plan = try!(self.get(template, in_, out));
+        plan.execute(in_, out)
+    }
+
+    /// Transform `buf` in place with a cached plan.
+    pub fn execute_inplace<T>(&self, template: &Template, buf: &mut [T]) -> PlanResult<()>
+        where T: FftData<T> + Zero
+    {
+        let plan = try!(self.get_inplace(template, buf));
+        plan.execute_inplace(buf)
+    }
+
+    fn lookup_or_plan<F>(&self, key: Key, f: F) -> PlanResult<Arc<CachedPlan>>
+        where F: FnOnce(&Key) -> PlanResult<(SharedPlan, usize)>
+    {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.clock += 1;
+            let now = inner.clock;
+            if let Some(e) = inner.plans.get_mut(&key) {
+                e.last_used = now;
+                return Ok(e.plan.clone())
+            }
+        }
+
+        // plan without holding the lock, since it can take a while.
+        let (plan, bytes) = try!(f(&key));
+        let plan = Arc::new(CachedPlan { plan: plan, key: key.clone() });
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let now = inner.clock;
+        // another thread may have planned the same thing meanwhile.
+        if let Some(e) = inner.plans.get_mut(&key) {
+            e.last_used = now;
+            return Ok(e.plan.clone())
+        }
+        inner.plans.insert(key, Entry { plan: plan.clone(), bytes: bytes, last_used: now });
+        inner.bytes += bytes;
+        inner.evict();
+        Ok(plan)
+    }
+}
+
+/// A zeroed buffer of `len` elements whose address has a given SIMD
+/// alignment.
+struct Scratch<T> {
+    buf: FftwVec<T>,
+    /// The offset of the elements, in bytes.
+    offset: usize,
+    len: usize,
+}
+
+impl<T: Zero> Scratch<T> {
+    fn new(len: usize, alignment: usize) -> PlanResult<Scratch<T>> {
+        let simd = ::mem::simd_alignment();
+        let size = cmp::max(mem::size_of::<T>(), 1);
+        // an element can be misaligned by less than its size (e.g. a
+        // `Complex64` 8 bytes past a 16-byte boundary), so try every
+        // offset the element type allows, in bytes.
+        let buf = FftwVec::<T>::zeros(len + (simd + size - 1) / size);
+        let step = mem::align_of::<T>();
+        let base = buf.as_ptr() as *const u8;
+        let offset = (0..simd / step).map(|i| i * step).find(|&k| unsafe {
+            alignment_of(base.offset(k as isize) as *const T) == alignment
+        });
+        match offset {
+            Some(k) => Ok(Scratch { buf: buf, offset: k, len: len }),
+            None => Err(Error::Unsupported("buffer alignment that the element type can't have")),
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        // the allocation is zeroed, and zero bytes are a zero of
+        // every element type FFTW transforms, so the elements are
+        // valid wherever they start.
+        unsafe {
+            let base = (self.buf.as_mut_ptr() as *mut u8).offset(self.offset as isize);
+            slice::from_raw_parts_mut(base as *mut T, self.len)
+        }
+    }
+}
+
+static GLOBAL_INIT: Once = ONCE_INIT;
+static mut GLOBAL: *const PlanCache = 0 as *const PlanCache;
+
+/// The cache shared by every thread.
+pub fn global() -> &'static PlanCache {
+    unsafe {
+        GLOBAL_INIT.call_once(|| {
+            let cache = Box::new(PlanCache::new(DEFAULT_EVICTION));
+            GLOBAL = mem::transmute::<Box<PlanCache>, *const PlanCache>(cache);
+        });
+        &*GLOBAL
+    }
+}
+
+thread_local!(static LOCAL: PlanCache = PlanCache::new(DEFAULT_EVICTION));
+
+/// Run `f` with the current thread's cache.
+pub fn with_local<A, F: FnOnce(&PlanCache) -> A>(f: F) -> A {
+    LOCAL.with(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{mem, slice};
+    use std::sync::Arc;
+    use std::thread;
+    use num::complex::Complex64;
+    use mem::FftwVec;
+    use error::{Buffer, Error};
+    use super::super::{Direction, Template};
+    use super::{Eviction, PlanCache, global, with_local};
+
+    #[test]
+    fn reuses_plans() {
+        let cache = PlanCache::new(Eviction::Count(2));
+        let template = Template::new()._1d(8);
+        let mut in_ = FftwVec::<f64>::zeros(8);
+        let mut out = FftwVec::<Complex64>::zeros(5);
+
+        let a = cache.get(&template, &in_, &out).unwrap();
+        let b = cache.get(&template, &in_, &out).unwrap();
+        assert!(&*a as *const _ == &*b as *const _);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(a.key().template, template);
+        assert!(!a.key().inplace);
+
+        in_[0] = 1.0;
+        a.execute(&mut in_, &mut out).unwrap();
+        assert!(out.iter().all(|x| (*x - Complex64::new(1.0, 0.0)).norm() < 1e-10));
+
+        let mut short = FftwVec::<Complex64>::zeros(4);
+        assert_eq!(a.execute(&mut in_, &mut short).err(),
+                   Some(Error::BufferTooSmall { buffer: Buffer::Output, required: 5, actual: 4 }));
+        let mut c = FftwVec::<Complex64>::zeros(8);
+        assert!(a.execute_inplace(&mut c).is_err());
+    }
+
+    #[test]
+    fn complex_misaligned_by_half() {
+        // a `Complex64` can start 8 bytes past a 16-byte boundary,
+        // which no whole number of elements from an aligned address
+        // reaches.
+        let mut backing = FftwVec::<f64>::zeros(2 * 8 + 1);
+        let buf = unsafe {
+            slice::from_raw_parts_mut(backing.as_mut_ptr().offset(1) as *mut Complex64, 8)
+        };
+        buf[0] = Complex64::new(1.0, 0.0);
+
+        let cache = PlanCache::new(Eviction::Count(2));
+        cache.execute_inplace(&Template::new()._1d(8), buf).unwrap();
+        assert!(buf.iter().all(|x| (*x - Complex64::new(1.0, 0.0)).norm() < 1e-10));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = PlanCache::new(Eviction::Count(2));
+        let mut buf = FftwVec::<Complex64>::zeros(16);
+        for &n in [4, 8, 4, 16].iter() {
+            cache.execute_inplace(&Template::new()._1d(n), &mut buf[..n]).unwrap();
+        }
+        // 8 was used least recently.
+        assert_eq!(cache.len(), 2);
+        let mut keys = cache.inner.lock().unwrap().plans.keys()
+            .map(|k| k.template.dims()[0].n).collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, [4, 16]);
+
+        // the in-place plans have one buffer each: 16 complex numbers
+        // of 16 bytes is 256 bytes, leaving no room for the 64 bytes
+        // of the older 4-element plan.
+        let bytes = 16 * mem::size_of::<Complex64>();
+        assert_eq!(bytes, 256);
+        cache.set_eviction(Eviction::Memory(bytes));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.memory(), bytes);
+        cache.clear();
+        assert_eq!((cache.len(), cache.memory()), (0, 0));
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let template = Arc::new(Template::new().direction(Direction::Backward)._1d(32));
+        let threads = (0..4).map(|i| {
+            let template = template.clone();
+            thread::spawn(move || {
+                let mut in_ = FftwVec::<Complex64>::zeros(32);
+                let mut out = FftwVec::<Complex64>::zeros(32);
+                in_[0] = Complex64::new(i as f64, 0.0);
+                global().execute(&template, &mut in_, &mut out).unwrap();
+                with_local(|c| c.execute(&template, &mut in_, &mut out)).unwrap();
+                assert!(out.iter().all(|x| (*x - in_[0]).norm() < 1e-10));
+            })
+        }).collect::<Vec<_>>();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert!(global().len() >= 1);
+    }
+}
@@ -3,18 +3,28 @@ use num::Complex;
 use libc::c_int;
 use ffi;
 
-use strided::{MutStrided, MutStride};
+use strided::MutStrided;
 
 use plan::RawPlan;
-use super::{FftData,  Meta, Secret, Inplace, Io, FftSpec, PlanResult, do_plan,
-            Dim, PlanningError, Ready, R2R};
+use error::{Buffer, Error};
+use super::{FftData,  Meta, Secret, View, Inplace, Io, FftSpec, PlanResult, do_plan, alignment_of,
+            Dim, Direction, Kind, Ready, R2R};
 
 impl<T: FftData<T>, I: MutStrided<Elem = T>> FftSpec for Inplace<I> {
     type Input = T;
     type Output = T;
     #[doc(hidden)]
     unsafe fn plan(&mut self, meta: &Meta) -> PlanResult<RawPlan> {
-        FftData::plan(self.in_out.as_stride_mut(), None::<MutStride<T>>, meta)
+        FftData::plan(View::new(self.in_out.as_stride_mut()), None::<View<T>>, meta)
+    }
+    #[doc(hidden)]
+    fn kind() -> Kind { <T as FftData<T>>::kind() }
+    #[doc(hidden)]
+    fn layout(&mut self) -> (usize, Option<usize>, Dim) {
+        let mut in_out = self.in_out.as_stride_mut();
+        let stride = in_out.stride() as isize;
+        (alignment_of(in_out.as_mut_ptr()), None,
+         Dim { n: in_out.len(), in_stride: stride, out_stride: stride })
     }
 
     #[doc(hidden)]
@@ -25,60 +35,83 @@ impl<U, T: FftData<U>, I: MutStrided<Elem = T>, O: MutStrided<Elem = U>> FftSpec
     type Output = U;
     #[doc(hidden)]
     unsafe fn plan(&mut self, meta: &Meta) -> PlanResult<RawPlan> {
-        let in_ = self.in_.as_stride_mut();
-        let out = self.out.as_stride_mut();
+        let in_ = View::new(self.in_.as_stride_mut());
+        let out = View::new(self.out.as_stride_mut());
 
         FftData::plan(in_, Some(out), meta)
     }
     #[doc(hidden)]
+    fn kind() -> Kind { <T as FftData<U>>::kind() }
+    #[doc(hidden)]
+    fn layout(&mut self) -> (usize, Option<usize>, Dim) {
+        let mut in_ = self.in_.as_stride_mut();
+        let mut out = self.out.as_stride_mut();
+        (alignment_of(in_.as_mut_ptr()), Some(alignment_of(out.as_mut_ptr())),
+         Dim { n: in_.len(), in_stride: in_.stride() as isize, out_stride: out.stride() as isize })
+    }
+    #[doc(hidden)]
     fn secret() -> Secret { Secret(()) }
 }
 
 
 
+/// Find the pointers to give FFTW for a transform from `in_` to `out`
+/// (or in-place, if `None`), checking that everything the layout
+/// touches lies inside the buffers.
+unsafe fn locate<T, U>(in_: &View<T>, out: &Option<View<U>>,
+                       dims: &[Dim], howmany: &[Dim], kind: Kind) -> PlanResult<(*mut T, *mut U)> {
+    let (in_span, out_span) = try!(Dim::extent(dims, howmany, kind));
+
+    let in_ptr = try!(in_span.locate(Buffer::Input, in_.ptr, in_.len, in_.stride));
+    let out_ptr = match *out {
+        Some(ref o) => try!(out_span.locate(Buffer::Output, o.ptr, o.len, o.stride)),
+        None => {
+            let out_ptr = try!(out_span.locate(Buffer::Output, in_.ptr, in_.len, in_.stride));
+            if out_ptr as *mut T != in_ptr {
+                return Err(Error::Unsupported("in-place transform with input and output \
+                                               reversed differently"))
+            }
+            out_ptr as *mut U
+        }
+    };
+    Ok((in_ptr, out_ptr))
+}
+
+/// The single dimension used when none are given: the whole buffer.
+fn default_dims<T, U>(in_: &View<T>, out: &Option<View<U>>) -> [Dim; 1] {
+    let out_stride = match *out {
+        Some(ref o) => o.stride,
+        None => in_.stride,
+    };
+    [Dim { n: in_.len, in_stride: in_.stride as isize, out_stride: out_stride as isize }]
+}
+
 impl FftData<Complex<f64>> for Complex<f64> {
     type State = Ready;
 
     #[doc(hidden)]
-    unsafe fn plan(mut in_: MutStride<Complex<f64>>, mut out: Option<MutStride<Complex<f64>>>,
+    unsafe fn plan(in_: View<Complex<f64>>, out: Option<View<Complex<f64>>>,
                    meta: &Meta)-> PlanResult<RawPlan> {
-        let in_ptr = in_.as_mut_ptr() as *mut _;
-        let in_len = in_.len();
-        let in_stride = in_.stride();
-        let (out_ptr, out_len, out_stride) = match out {
-            Some(ref mut o) => (o.as_mut_ptr() as *mut _, o.len(), o.stride()),
-            None => (in_ptr, in_len, in_stride),
-        };
-
         let use_default_length = meta.dims.is_empty();
-        let default = [Dim { n: in_len, in_stride: in_stride, out_stride: out_stride }];
+        let default = default_dims(&in_, &out);
+        let dims = if use_default_length { &default[..] } else { &*meta.dims };
 
-        let required_len = if use_default_length {
-            in_len
-        } else {
-            Dim::size(&*meta.dims).0
-        };
+        let (in_ptr, out_ptr) = try!(locate(&in_, &out, dims, &*meta.howmany, Kind::C2c));
 
-        if in_len < required_len || out_len < required_len {
-            // insufficient space
-            return Err(PlanningError::BufferTooSmall(in_len, out_len, meta.dims.clone()))
-        }
+        let (rank, dims_ptr) = (dims.len() as c_int, dims.as_ptr() as *const _);
 
-        let (rank,dims) = if use_default_length {
-            (1, default.as_ptr() as *const _)
-        } else {
-            (meta.dims.len() as c_int, meta.dims.as_ptr() as *const _)
-        };
-
-        do_plan(|| {
+        do_plan(meta, Kind::C2c, dims, in_ptr == out_ptr, || {
             ffi::fftw_plan_guru64_dft(
-                rank, dims,
+                rank, dims_ptr,
                 meta.howmany.len() as c_int, meta.howmany.as_ptr() as *const _,
-                in_ptr, out_ptr,
-                meta.direction.sign(), meta.rigor.flags())
+                in_ptr as *mut _, out_ptr as *mut _,
+                meta.sign(), meta.flags())
         })
     }
 
+    #[doc(hidden)]
+    fn kind() -> Kind { Kind::C2c }
+
     #[doc(hidden)]
     fn secret() -> Secret { Secret(()) }
 }
@@ -88,35 +121,33 @@ impl FftData<Complex<f64>> for f64 {
     type State = Ready;
 
     #[doc(hidden)]
-    unsafe fn plan(mut in_: MutStride<f64>, mut out: Option<MutStride<Complex<f64>>>,
+    unsafe fn plan(in_: View<f64>, out: Option<View<Complex<f64>>>,
                    meta: &Meta) -> PlanResult<RawPlan> {
-        let in_ptr = in_.as_mut_ptr();
-        let in_len = in_.len();
-
-        let (out_ptr, out_len) = match out {
-            Some(ref mut o) => (o.as_mut_ptr() as *mut _, o.len()),
-            None => unimplemented!(/*"in-place r2c is hard"*/)
-        };
+        if out.is_none() {
+            return Err(Error::Unsupported("in-place real-to-complex"))
+        }
+        try!(meta.check_direction(Some(Direction::Forward), "backward real-to-complex"));
 
         let use_default_length = meta.dims.is_empty();
         if use_default_length {
-            return Err(PlanningError::NoLengthNoDefault)
+            return Err(Error::NoLengthNoDefault)
         }
 
-        let (r_size, c_size) = Dim::size(&*meta.dims);
-        if in_len < r_size || out_len < c_size {
-            return Err(PlanningError::BufferTooSmall(in_len, out_len, meta.dims.clone()));
-        }
+        let (in_ptr, out_ptr) = try!(locate(&in_, &out,
+                                            &*meta.dims, &*meta.howmany, Kind::R2c));
 
-        do_plan(|| {
+        do_plan(meta, Kind::R2c, &meta.dims, in_ptr as usize == out_ptr as usize, || {
             ffi::fftw_plan_guru64_dft_r2c(
                 meta.dims.len() as c_int, meta.dims.as_ptr() as *const _,
                 meta.howmany.len() as c_int, meta.howmany.as_ptr() as *const _,
-                in_ptr, out_ptr,
-                meta.rigor.flags())
+                in_ptr, out_ptr as *mut _,
+                meta.flags())
         })
     }
 
+    #[doc(hidden)]
+    fn kind() -> Kind { Kind::R2c }
+
     #[doc(hidden)]
     fn secret() -> Secret { Secret(()) }
 }
@@ -124,35 +155,33 @@ impl FftData<f64> for Complex<f64> {
     type State = Ready;
 
     #[doc(hidden)]
-    unsafe fn plan(mut in_: MutStride<Complex<f64>>, mut out: Option<MutStride<f64>>,
+    unsafe fn plan(in_: View<Complex<f64>>, out: Option<View<f64>>,
                    meta: &Meta) -> PlanResult<RawPlan> {
-        let in_ptr = in_.as_mut_ptr() as *mut _;
-        let in_len = in_.len();
-
-        let (out_ptr, out_len) = match out {
-            Some(ref mut o) => (o.as_mut_ptr(), o.len()),
-            None => unimplemented!(/*"in-place c2r is hard"*/)
-        };
+        if out.is_none() {
+            return Err(Error::Unsupported("in-place complex-to-real"))
+        }
+        try!(meta.check_direction(Some(Direction::Backward), "forward complex-to-real"));
 
         let use_default_length = meta.dims.is_empty();
         if use_default_length {
-            return Err(PlanningError::NoLengthNoDefault)
+            return Err(Error::NoLengthNoDefault)
         }
 
-        let (r_size, c_size) = Dim::size(&*meta.dims);
-        if in_len < c_size || out_len < r_size {
-            return Err(PlanningError::BufferTooSmall(in_len, out_len, meta.dims.clone()));
-        }
+        let (in_ptr, out_ptr) = try!(locate(&in_, &out,
+                                            &*meta.dims, &*meta.howmany, Kind::C2r));
 
-        do_plan(|| {
+        do_plan(meta, Kind::C2r, &meta.dims, in_ptr as usize == out_ptr as usize, || {
             ffi::fftw_plan_guru64_dft_c2r(
                 meta.dims.len() as c_int, meta.dims.as_ptr() as *const _,
                 meta.howmany.len() as c_int, meta.howmany.as_ptr() as *const _,
-                in_ptr, out_ptr,
-                meta.rigor.flags())
+                in_ptr as *mut _, out_ptr,
+                meta.flags())
         })
     }
 
+    #[doc(hidden)]
+    fn kind() -> Kind { Kind::C2r }
+
     #[doc(hidden)]
     fn secret() -> Secret { Secret(()) }
 }
@@ -161,46 +190,39 @@ impl FftData<f64> for f64 {
     type State = R2R;
 
     #[doc(hidden)]
-    unsafe fn plan(mut in_: MutStride<f64>, mut out: Option<MutStride<f64>>,
+    unsafe fn plan(in_: View<f64>, out: Option<View<f64>>,
                    meta: &Meta) -> PlanResult<RawPlan> {
-        let in_ptr = in_.as_mut_ptr() as *mut _;
-        let in_len = in_.len();
-        let in_stride = in_.stride();
-        let (out_ptr, out_len, out_stride) = match out {
-            Some(ref mut o) => (o.as_mut_ptr() as *mut _, o.len(), o.stride()),
-            None => (in_ptr, in_len, in_stride),
-        };
+        try!(meta.check_direction(None, "real-to-real transforms have no direction"));
 
         let use_default_length = meta.dims.is_empty();
-        let default = [Dim { n: in_len, in_stride: in_stride, out_stride: out_stride }];
+        let default = default_dims(&in_, &out);
+        let dims = if use_default_length { &default[..] } else { &*meta.dims };
 
-        let required_len = if use_default_length {
-            in_len
-        } else {
-            Dim::size(&*meta.dims).0
-        };
-        if in_len < required_len || out_len < required_len {
-            // insufficient space
-            return Err(PlanningError::BufferTooSmall(in_len, out_len, meta.dims.clone()))
+        let (in_ptr, out_ptr) = try!(locate(&in_, &out, dims, &*meta.howmany, Kind::R2r));
+        let (rank, dims_ptr) = (dims.len() as c_int, dims.as_ptr() as *const _);
+
+        if meta.r2r_kinds.len() != rank as usize && meta.r2r_kinds.len() != 1 {
+            return Err(Error::InvalidArgument("r2r_kinds: need one kind, or one per dimension"))
         }
-        let (rank,dims) = if use_default_length {
-            (1, default.as_ptr() as *const _)
+        // FFTW requires a kind for every dimension.
+        let kinds = if meta.r2r_kinds.len() == rank as usize {
+            meta.r2r_kinds.clone()
         } else {
-            (meta.dims.len() as c_int, meta.dims.as_ptr() as *const _)
+            vec![meta.r2r_kinds[0]; rank as usize]
         };
-
-        assert!(meta.r2r_kinds.len() == rank as usize || meta.r2r_kinds.len() == 1);
-        let kinds = meta.r2r_kinds.as_ptr();
-        do_plan(|| {
+        do_plan(meta, Kind::R2r, dims, in_ptr == out_ptr, || {
             ffi::fftw_plan_guru64_r2r(
-                rank, dims,
+                rank, dims_ptr,
                 meta.howmany.len() as c_int, meta.howmany.as_ptr() as *const _,
                 in_ptr, out_ptr,
-                kinds, meta.rigor.flags()
+                kinds.as_ptr(), meta.flags()
                 )
         })
     }
 
+    #[doc(hidden)]
+    fn kind() -> Kind { Kind::R2r }
+
     #[doc(hidden)]
     fn secret() -> Secret { Secret(()) }
 }
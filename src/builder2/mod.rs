@@ -2,30 +2,61 @@
 use ffi;
 use libc::{c_uint, c_int, c_void};
 use num::complex::{Complex, Complex64};
+use std::{cmp, mem};
 use std::marker::PhantomData;
 use strided::{MutStrided, Strided, MutStride};
 
+use mem::FftwArray;
 use plan::RawPlan;
+use threading::{self, Threads};
+use error::{self, Buffer, Error};
+
+pub use error::PlanResult;
+/// The old name for `Error`.
+pub use error::Error as PlanningError;
 
 /// Values for which `[Self] -> [Target]` works as a transform.
 pub trait FftData<Target> {
     type State;
     #[doc(hidden)]
-    unsafe fn plan(in_: MutStride<Self>, out: Option<MutStride<Target>>,
+    unsafe fn plan(in_: View<Self>, out: Option<View<Target>>,
                    meta: &Meta) -> PlanResult<RawPlan>;
 
+    #[doc(hidden)]
+    fn kind() -> Kind;
+
     #[doc(hidden)]
     fn secret() -> Secret;
 }
 
 mod fft_data;
+mod alloc;
+mod template;
+mod shared;
+pub mod cache;
 
+pub use self::alloc::Alloc;
+pub use self::template::Template;
+pub use self::shared::SharedPlan;
+
+/// Buffers that know the shape of the array they hold, so that the
+/// dimensions of a transform can be inferred from them.
+pub trait Shaped {
+    /// The shape of the contiguous row-major array.
+    fn shape(&self) -> &[usize];
+}
+impl<T> Shaped for FftwArray<T> {
+    fn shape(&self) -> &[usize] { FftwArray::shape(self) }
+}
+impl<'a, T> Shaped for &'a mut FftwArray<T> {
+    fn shape(&self) -> &[usize] { FftwArray::shape(*self) }
+}
 
 /// How much effort FFTW should put into computing the best strategy
 /// to use.
 ///
 /// The `FFTW_WISDOM_ONLY` rigor level is replaced by the
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Rigor {
     Estimate,
     Measure,
@@ -44,7 +75,7 @@ impl Rigor {
 }
 
 /// The direction of the transform to perform..
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
     Forward, Backward
 }
@@ -72,45 +103,158 @@ pub struct Inplace<I> {
 pub struct R2R(());
 pub struct Ready(());
 
+/// A dimension of a transform: its length, and the distance between
+/// consecutive elements of the input and output.
+///
+/// Strides can be negative, to traverse an axis in reverse.
 #[repr(C)]
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Dim {
     pub n: usize,
-    pub in_stride: usize,
-    pub out_stride: usize,
+    pub in_stride: isize,
+    pub out_stride: isize,
 }
 
 impl Dim {
-    // TODO: should be num_elems?
-    fn size(ds: &[Dim]) -> (usize, usize) {
-        assert!(ds.len() > 0);
-        let head = ds[.. ds.len() - 1].iter().fold(1, |m, d| m * d.n);
-        let last = ds.last().expect("no dims in Dim::num_elems");
-        (head * last.n, head * (last.n / 2 + 1))
-    }
-
-    // TODO: the checks in fft_size.rs need to use this
-    fn array_size(ds: &[Dim], c2r: bool, r2c: bool) -> (usize, usize) {
-        debug_assert!(!(c2r && r2c));
-        assert!(ds.len() > 0);
-
-        let (in_, out) = ds[.. ds.len() - 1].iter().fold((0, 0), |(a,b), d| {
-            (a + d.n * d.in_stride, b + d.n * d.out_stride)
-        });
-        let last = ds.last().unwrap();
-        if c2r {
-            (in_ + (last.n / 2 + 1) * last.in_stride,
-             out + last.n * last.out_stride)
-        } else if r2c {
-            (in_ + last.n * last.out_stride,
-             out + (last.n / 2 + 1) * last.out_stride)
+    /// The parts of the input and output buffers touched by a `kind`
+    /// transform with dimensions `ds` repeated over `howmany`, in
+    /// elements of the underlying buffers.
+    ///
+    /// The last dimension of the complex side of a real-to-complex or
+    /// complex-to-real transform only has `n / 2 + 1` elements.
+    fn extent(ds: &[Dim], howmany: &[Dim], kind: Kind) -> PlanResult<(Span, Span)> {
+        let last = ds.len().wrapping_sub(1);
+        // the total distance moved forwards and backwards from the
+        // first element, for each of the input and output.
+        let (mut in_fwd, mut in_back, mut out_fwd, mut out_back) = (0, 0, 0, 0);
+        for (i, d) in ds.iter().chain(howmany.iter()).enumerate() {
+            let (in_n, out_n) = match kind {
+                Kind::R2c if i == last => (d.n, d.n / 2 + 1),
+                Kind::C2r if i == last => (d.n / 2 + 1, d.n),
+                _ => (d.n, d.n),
+            };
+            if in_n == 0 || out_n == 0 {
+                // nothing is touched at all.
+                let empty = Span { base: 0, extent: 0 };
+                return Ok((empty, empty))
+            }
+            try!(step(&mut in_fwd, &mut in_back, in_n, d.in_stride));
+            try!(step(&mut out_fwd, &mut out_back, out_n, d.out_stride));
+        }
+
+        fn step(fwd: &mut usize, back: &mut usize, n: usize, stride: isize) -> PlanResult<()> {
+            let (total, dist) = if stride < 0 {
+                (back, (-(stride + 1)) as usize + 1)
+            } else {
+                (fwd, stride as usize)
+            };
+            let size = (n - 1).saturating_mul(dist).saturating_add(*total);
+            try!(error::check_isize(size));
+            *total = size;
+            Ok(())
+        }
+
+        fn span(fwd: usize, back: usize) -> PlanResult<Span> {
+            try!(error::check_isize(fwd + back));
+            Ok(Span { base: back, extent: fwd + back + 1 })
+        }
+        Ok((try!(span(in_fwd, in_back)), try!(span(out_fwd, out_back))))
+    }
+}
+
+/// The part of a buffer that a transform touches: `extent` elements
+/// of the underlying memory, the first element FFTW is given being
+/// `base` elements from the start (negative strides go backwards from
+/// there).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    base: usize,
+    extent: usize,
+}
+
+impl Span {
+    /// Check that a strided buffer with `len` elements `stride` apart
+    /// starting at `ptr` covers this span, returning the pointer to
+    /// give to FFTW.
+    unsafe fn locate<T>(self, buffer: Buffer,
+                        ptr: *mut T, len: usize, stride: usize) -> PlanResult<*mut T> {
+        let available = if len == 0 { 0 } else { (len - 1) * stride + 1 };
+        if self.extent <= available {
+            Ok(ptr.offset(self.base as isize))
         } else {
-            (in_, out)
+            // every offset accessed is a multiple of `stride`.
+            let required = (self.extent - 1) / stride + 1;
+            Err(Error::BufferTooSmall { buffer: buffer, required: required, actual: len })
+        }
+    }
+}
+
+/// The types of transform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Complex-to-complex.
+    C2c,
+    /// Real-to-complex.
+    R2c,
+    /// Complex-to-real.
+    C2r,
+    /// Real-to-real.
+    R2r,
+}
+
+impl Kind {
+    /// The sizes in bytes of the input and output elements.
+    fn elem_sizes(self) -> (usize, usize) {
+        let (real, complex) = (mem::size_of::<f64>(), mem::size_of::<Complex64>());
+        match self {
+            Kind::C2c => (complex, complex),
+            Kind::R2c => (real, complex),
+            Kind::C2r => (complex, real),
+            Kind::R2r => (real, real),
         }
     }
 }
 
-#[derive(Clone, Copy)]
+/// The memory needed by a transform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferRequirements {
+    /// The minimum number of elements in the input buffer.
+    pub input: usize,
+    /// The minimum number of elements in the output buffer (for an
+    /// in-place transform, the same memory viewed as output elements).
+    pub output: usize,
+    /// The alignment in bytes that FFTW's SIMD code needs; buffers
+    /// allocated by FFTW (e.g. `FftwVec`) always have it.
+    pub alignment: usize,
+}
+
+/// The buffers required by a `kind` transform with dimensions `dims`
+/// repeated over `howmany`, with strides in elements, as checked
+/// when planning.
+///
+/// An in-place transform needs a single buffer large enough for both
+/// the input and output, whose first elements must coincide.
+pub fn buffer_requirements(kind: Kind, dims: &[Dim], howmany: &[Dim],
+                           inplace: bool) -> PlanResult<BufferRequirements> {
+    if dims.is_empty() {
+        return Err(Error::InvalidArgument("buffer_requirements: empty dimensions"))
+    }
+    let (in_span, out_span) = try!(Dim::extent(dims, howmany, kind));
+    let (in_size, out_size) = kind.elem_sizes();
+    let (input, output) = if inplace {
+        if in_span.base * in_size != out_span.base * out_size {
+            return Err(Error::Unsupported("in-place transform with input and output \
+                                           reversed differently"))
+        }
+        let bytes = cmp::max(in_span.extent * in_size, out_span.extent * out_size);
+        ((bytes + in_size - 1) / in_size, (bytes + out_size - 1) / out_size)
+    } else {
+        (in_span.extent, out_span.extent)
+    };
+    Ok(BufferRequirements { input: input, output: output, alignment: ::mem::simd_alignment() })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum R2rKind {
     R2ch,
     Hc2r,
@@ -146,10 +290,19 @@ impl R2rKind {
 pub struct Meta {
     rigor: Rigor,
     wisdom_restriction: bool,
-    direction: Direction,
+    direction: Option<Direction>,
+    // extra FFTW flags.
+    flags: c_uint,
+    threads: Threads,
+
+    // an invalid argument given to the builder, reported when planning.
+    error: Option<Error>,
 
     in_stride: usize,
     out_stride: usize,
+    // the shapes of arrays given as the input and output.
+    in_shape: Option<Vec<usize>>,
+    out_shape: Option<Vec<usize>>,
 
     r2r_kinds: Vec<c_uint>,
     overall_dims: Vec<Dim>,
@@ -157,19 +310,97 @@ pub struct Meta {
     howmany: Vec<Dim>,
 }
 
-#[derive(Debug)]
-pub enum PlanningError {
-    FftwError,
-    NoLengthNoDefault,
-    BufferTooSmall(usize, usize, Vec<Dim>),
+impl Meta {
+    fn flags(&self) -> c_uint {
+        self.rigor.flags() | self.flags | if self.wisdom_restriction {
+            ffi::FFTW_WISDOM_ONLY
+        } else {
+            0
+        }
+    }
+
+    /// The sign for a complex transform, which defaults to forward.
+    fn sign(&self) -> c_int {
+        self.direction.unwrap_or(Direction::Forward).sign()
+    }
+
+    /// Fail if a direction other than `allowed` was explicitly set for
+    /// a transform that only goes one way (or, with `None`, none at all).
+    fn check_direction(&self, allowed: Option<Direction>, what: &'static str) -> PlanResult<()> {
+        match (self.direction, allowed) {
+            (None, _) => Ok(()),
+            (Some(Direction::Forward), Some(Direction::Forward)) |
+            (Some(Direction::Backward), Some(Direction::Backward)) => Ok(()),
+            _ => Err(Error::Unsupported(what)),
+        }
+    }
+
+    /// The dimensions of a contiguous `kind` transform between the
+    /// arrays given as the input and output, if either has a shape.
+    ///
+    /// The shape of the real array is the logical size of a
+    /// real-to-complex or complex-to-real transform, and the complex
+    /// one must have `n / 2 + 1` elements along the last axis.
+    fn shaped_dims(&self, kind: Kind) -> PlanResult<Option<Vec<Dim>>> {
+        if self.in_shape.is_none() && self.out_shape.is_none() {
+            return Ok(None)
+        }
+        let logical = match kind {
+            Kind::R2c => self.in_shape.clone(),
+            Kind::C2r => self.out_shape.clone(),
+            Kind::C2c | Kind::R2r => self.in_shape.clone().or(self.out_shape.clone()),
+        };
+        let logical = match logical {
+            Some(s) => s,
+            // only the complex side has a shape, which is ambiguous.
+            None => return Err(Error::NoLengthNoDefault)
+        };
+
+        let mut half = logical.clone();
+        if let Some(n) = half.last_mut() {
+            *n = *n / 2 + 1
+        }
+        let (in_shape, out_shape) = match kind {
+            Kind::R2c => (logical.clone(), half),
+            Kind::C2r => (half, logical.clone()),
+            Kind::C2c | Kind::R2r => (logical.clone(), logical.clone()),
+        };
+        if self.in_shape.as_ref().map_or(false, |s| *s != in_shape) ||
+            self.out_shape.as_ref().map_or(false, |s| *s != out_shape) {
+            return Err(Error::InvalidArgument("array shapes don't match the transform"))
+        }
+
+        fn row_major(shape: &[usize]) -> Vec<isize> {
+            let mut strides = vec![0; shape.len()];
+            let mut stride = 1;
+            for (s, &n) in strides.iter_mut().zip(shape.iter()).rev() {
+                *s = stride;
+                stride *= n as isize;
+            }
+            strides
+        }
+        let (in_strides, out_strides) = (row_major(&in_shape), row_major(&out_shape));
+        Ok(Some(logical.iter().zip(in_strides.iter().zip(out_strides.iter())).map(|(&n, (&i, &o))| {
+            Dim { n: n, in_stride: i, out_stride: o }
+        }).collect()))
+    }
 }
 
-pub type PlanResult<T> = Result<T, PlanningError>;
+/// The SIMD alignment of `p`, as FFTW sees it.
+fn alignment_of<T>(p: *const T) -> usize {
+    unsafe { ffi::fftw_alignment_of(p as *mut f64) as usize }
+}
 
-fn do_plan<F: FnOnce() -> ffi::fftw_plan>(f: F) -> PlanResult<RawPlan> {
-    match RawPlan::new(f) {
-        Some(p) => Ok(p),
-        None => Err(PlanningError::FftwError),
+/// Plan with `f`, which may be called several times to choose the
+/// number of threads.
+fn do_plan<F: Fn() -> ffi::fftw_plan>(meta: &Meta, kind: Kind, dims: &[Dim], inplace: bool,
+                                      f: F) -> PlanResult<RawPlan> {
+    match meta.threads {
+        Threads::Fixed(n) => threading::plan_with_threads(n, meta.flags(), &f),
+        Threads::Auto(max) => {
+            let key = threading::descriptor(kind, dims, &meta.howmany, inplace, meta.flags());
+            threading::plan_auto(key, max, meta.flags(), &f)
+        }
     }
 }
 
@@ -179,6 +410,23 @@ fn do_plan<F: FnOnce() -> ffi::fftw_plan>(f: F) -> PlanResult<RawPlan> {
 #[doc(hidden)]
 pub struct Secret(());
 
+/// A buffer for planning: where it starts, and how many elements it
+/// has how far apart.
+///
+/// This is only pointers, so that an in-place transform can be
+/// described without two mutable borrows of the same memory.
+#[doc(hidden)]
+pub struct View<T> {
+    ptr: *mut T,
+    len: usize,
+    stride: usize,
+}
+impl<T> View<T> {
+    fn new(mut s: MutStride<T>) -> View<T> {
+        View { ptr: s.as_mut_ptr(), len: s.len(), stride: s.stride() }
+    }
+}
+
 pub struct Planner<Stage, State> {
     meta: Meta,
     data: Stage,
@@ -192,7 +440,10 @@ impl Planner<Begin, Begin> {
             meta: Meta {
                 rigor: Rigor::Estimate,
                 wisdom_restriction: false,
-                direction: Direction::Forward,
+                direction: None,
+                flags: 0,
+                threads: Threads::Fixed(1),
+                error: None,
 
                 r2r_kinds: vec![],
 
@@ -202,6 +453,8 @@ impl Planner<Begin, Begin> {
 
                 in_stride: 1,
                 out_stride: 1,
+                in_shape: None,
+                out_shape: None,
             },
 
             data: Begin(()),
@@ -212,8 +465,10 @@ impl Planner<Begin, Begin> {
 }
 
 impl<Y> Planner<Begin, Y> {
+    /// Set the input buffer.
     pub fn input<I: MutStrided>(mut self, in_: I) -> Planner<Input<I>, Begin> {
         self.meta.in_stride = in_.stride();
+        self.meta.in_shape = None;
 
         Planner {
             meta: self.meta,
@@ -221,6 +476,15 @@ impl<Y> Planner<Begin, Y> {
             _marker: PhantomData
         }
     }
+
+    /// Set the input buffer to an array, so that the dimensions of
+    /// the transform default to those of the array.
+    pub fn input_array<I: MutStrided + Shaped>(self, in_: I) -> Planner<Input<I>, Begin> {
+        let shape = in_.shape().to_vec();
+        let mut planner = self.input(in_);
+        planner.meta.in_shape = Some(shape);
+        planner
+    }
 }
 
 impl<X, Y> Planner<X, Y> {
@@ -237,21 +501,51 @@ impl<X, Y> Planner<X, Y> {
     }
 
     /// Set the direction of the transform to perform.
+    ///
+    /// This only applies to complex-to-complex transforms: setting a
+    /// direction for a real-to-real transform, or the wrong one for a
+    /// real-to-complex (forward) or complex-to-real (backward)
+    /// transform, is an error.
     pub fn direction(mut self, direction: Direction) -> Planner<X, Y> {
-        self.meta.direction = direction;
+        self.meta.direction = Some(direction);
+        self
+    }
+
+    /// Set how many threads the plan uses, which defaults to one.
+    pub fn threads(mut self, threads: Threads) -> Planner<X, Y> {
+        self.meta.threads = threads;
+        self
+    }
+
+    /// Set extra FFTW flags, e.g. `FFTW_DESTROY_INPUT`.
+    pub fn flags(mut self, flags: c_uint) -> Planner<X, Y> {
+        self.meta.flags = flags;
         self
     }
+
+    /// Repeat the transform over the given dimensions, with strides
+    /// giving the distance between each transform, in elements of
+    /// the buffers (like the strides of the dimensions).
+    pub fn howmany(mut self, dims: &[Dim]) -> Planner<X, Y> {
+        self.meta.howmany = dims.to_vec();
+        self
+    }
+    /// The dimensions the transform is repeated over.
+    pub fn howmany_dims(&self) -> &[Dim] {
+        &self.meta.howmany
+    }
 }
 
 impl<I: MutStrided, Y> Planner<Input<I>, Y> {
-    pub fn output<O: MutStrided>(mut self, out: O)
-                                       -> Planner<Io<I, O>,
+    /// Set the output buffer.
+    pub fn output<O>(mut self, out: O) -> Planner<Io<I, O>,
                                                   <I::Elem as FftData<O::Elem>>::State>
         where O: MutStrided,
               I::Elem: FftData<O::Elem>
     {
 
         self.meta.out_stride = out.stride();
+        self.meta.out_shape = None;
 
         Planner {
             meta: self.meta,
@@ -259,6 +553,19 @@ impl<I: MutStrided, Y> Planner<Input<I>, Y> {
             _marker: PhantomData
         }
     }
+
+    /// Set the output buffer to an array, so that the dimensions of
+    /// the transform default to those of the array.
+    pub fn output_array<O>(self, out: O) -> Planner<Io<I, O>,
+                                                    <I::Elem as FftData<O::Elem>>::State>
+        where O: MutStrided + Shaped,
+              I::Elem: FftData<O::Elem>
+    {
+        let shape = out.shape().to_vec();
+        let mut planner = self.output(out);
+        planner.meta.out_shape = Some(shape);
+        planner
+    }
 }
 
 impl<I: MutStrided, Y> Planner<Input<I>, Y>
@@ -266,6 +573,7 @@ impl<I: MutStrided, Y> Planner<Input<I>, Y>
 {
     pub fn inplace(mut self) -> Planner<Inplace<I>, <I::Elem as FftData<I::Elem>>::State>  {
         self.meta.out_stride = self.meta.in_stride;
+        self.meta.out_shape = self.meta.in_shape.clone();
 
         Planner {
             meta: self.meta,
@@ -293,7 +601,9 @@ impl<X, Y> Planner<X, Y>
     }
 
     pub fn nd(mut self, dims: &[usize]) -> Planner<X, Y> {
-        assert!(dims.len() > 0, "Planner.nd: empty dimensions");
+        if dims.is_empty() {
+            self.meta.error = Some(Error::InvalidArgument("Planner.nd: empty dimensions"));
+        }
         self.meta.dims.clear();
         self.meta.dims.extend(dims.iter().map(|n| Dim { n: *n, in_stride: 0, out_stride: 0 }));
 
@@ -301,14 +611,44 @@ impl<X, Y> Planner<X, Y>
         for place in self.meta.dims.iter_mut().rev() {
             place.in_stride = stride;
             place.out_stride = stride;
-            stride *= place.n;
+            stride *= place.n as isize;
         }
         self
     }
 
-    pub fn nd_subarray(mut self, dims: &[(usize, usize, usize)]) -> Planner<X, Y> {
-        assert!(dims.len() > 0,
-                "Planner.nd_subarray: empty dimensions");
+    /// Plan a transform of a subarray of a larger row-major array.
+    ///
+    /// Each element of `dims` is `(n, in_, out)`: the transform is of
+    /// length `n` along that axis, and the input and output arrays have
+    /// lengths `|in_|` and `|out|`. A negative `in_` or `out` means
+    /// that axis of the input or output is traversed in reverse.
+    ///
+    /// The arrays must be at least as long as the transform along
+    /// each axis (except that the complex side of a real-to-complex or
+    /// complex-to-real transform only needs `n / 2 + 1` along the
+    /// last).
+    pub fn nd_subarray(mut self, dims: &[(usize, isize, isize)]) -> Planner<X, Y> {
+        if dims.is_empty() {
+            self.meta.error = Some(Error::InvalidArgument("Planner.nd_subarray: empty dimensions"));
+        }
+        let kind = <<<X as HasInput>::I as Strided>::Elem
+                    as FftData<<<X as HasOutput>::O as Strided>::Elem>>::kind();
+        for (i, &(n, in_, out)) in dims.iter().enumerate() {
+            let last = i + 1 == dims.len();
+            let (in_n, out_n) = match kind {
+                Kind::R2c if last => (n, n / 2 + 1),
+                Kind::C2r if last => (n / 2 + 1, n),
+                _ => (n, n),
+            };
+            if in_ == 0 || out == 0 {
+                self.meta.error = Some(Error::InvalidArgument("Planner.nd_subarray: \
+                                                               zero array length"));
+            } else if in_n > in_.abs() as usize || out_n > out.abs() as usize {
+                self.meta.error = Some(Error::InvalidArgument("Planner.nd_subarray: \
+                                                               array shorter than the \
+                                                               transform"));
+            }
+        }
 
         self.meta.dims.clear();
         self.meta.dims.extend(dims.iter().map(|n| Dim { n: n.0 , in_stride: 0, out_stride: 0 }));
@@ -316,15 +656,29 @@ impl<X, Y> Planner<X, Y>
         let mut in_stride = 1;
         let mut out_stride = 1;
         for (place, &(_, in_, out)) in self.meta.dims.iter_mut().zip(dims.iter()).rev() {
-            place.in_stride = in_stride;
-            place.out_stride = out_stride;
+            place.in_stride = in_stride * in_.signum();
+            place.out_stride = out_stride * out.signum();
 
-            in_stride *= in_;
-            out_stride *= out;
+            in_stride *= in_.abs();
+            out_stride *= out.abs();
         }
         debug!("dimensions & strides: {:?}", self.meta.dims);
         self
     }
+
+    /// Plan a transform with arbitrary dimensions and strides, in
+    /// units of elements of the (possibly strided) input and output.
+    ///
+    /// A negative stride traverses that axis in reverse, starting from
+    /// the end of the buffer.
+    pub fn nd_strided(mut self, dims: &[Dim]) -> Planner<X, Y> {
+        if dims.is_empty() {
+            self.meta.error = Some(Error::InvalidArgument("Planner.nd_strided: empty dimensions"));
+        }
+        self.meta.dims.clear();
+        self.meta.dims.extend(dims.iter().cloned());
+        self
+    }
 }
 
 pub trait HasInput {
@@ -364,6 +718,15 @@ pub trait FftSpec {
     #[doc(hidden)]
     unsafe fn plan(&mut self, meta: &Meta) -> PlanResult<RawPlan>;
 
+    #[doc(hidden)]
+    fn kind() -> Kind;
+
+    /// The SIMD alignments of the input and output (`None` if the
+    /// transform is in-place), and the dimension used when none are
+    /// given.
+    #[doc(hidden)]
+    fn layout(&mut self) -> (usize, Option<usize>, Dim);
+
     #[doc(hidden)]
     fn secret() -> Secret;
 }
@@ -374,7 +737,9 @@ impl<X: FftSpec<Input=f64, Output=f64>> Planner<X, R2R> {
         self.r2r_kinds(&[kind])
     }
     pub fn r2r_kinds(mut self, kinds: &[R2rKind]) -> Planner<X, Ready> {
-        assert!(kinds.len() > 0, "Planner.r2r_kinds: no kinds");
+        if kinds.is_empty() {
+            self.meta.error = Some(Error::InvalidArgument("Planner.r2r_kinds: no kinds"));
+        }
 
         self.meta.r2r_kinds.clear();
         self.meta.r2r_kinds.extend(kinds.iter().map(|k| k.as_fftw()));
@@ -388,11 +753,27 @@ impl<X: FftSpec<Input=f64, Output=f64>> Planner<X, R2R> {
 }
 
 impl<X: FftSpec> Planner<X, Ready> {
-    pub fn plan(mut self) -> Result<Plan<X>, PlanningError> {
-        // space things out appropriately for the backing array.
-        for d in self.meta.dims.iter_mut() {
-            d.in_stride *= self.meta.in_stride;
-            d.out_stride *= self.meta.out_stride;
+    pub fn plan(mut self) -> PlanResult<Plan<X>> {
+        if let Some(e) = self.meta.error.take() {
+            return Err(e)
+        }
+
+        if self.meta.dims.is_empty() {
+            if let Some(dims) = try!(self.meta.shaped_dims(X::kind())) {
+                self.meta.dims = dims;
+            }
+        }
+
+        // space things out appropriately for the backing array: the
+        // strides of both the dimensions and the repeats are in
+        // elements of the buffers, which may be strided themselves.
+        let (in_stride, out_stride) = (self.meta.in_stride as isize, self.meta.out_stride as isize);
+        for d in self.meta.dims.iter_mut().chain(self.meta.howmany.iter_mut()) {
+            d.in_stride = try!(scale_stride(d.in_stride, in_stride));
+            d.out_stride = try!(scale_stride(d.out_stride, out_stride));
+        }
+        for d in self.meta.dims.iter().chain(self.meta.howmany.iter()) {
+            try!(error::check_isize(d.n));
         }
 
         match unsafe {self.data.plan(&self.meta)} {
@@ -402,14 +783,25 @@ impl<X: FftSpec> Planner<X, Ready> {
     }
 }
 
+/// `stride * scale`, for a positive `scale`, failing with the
+/// magnitude of the product (saturated) if it overflows.
+fn scale_stride(stride: isize, scale: isize) -> PlanResult<isize> {
+    match stride.checked_mul(scale) {
+        Some(s) => Ok(s),
+        None => {
+            let magnitude = if stride < 0 { (-(stride + 1)) as usize + 1 } else { stride as usize };
+            let size = magnitude.saturating_mul(scale as usize);
+            Err(Error::SizeOverflow { size: size, max: ::std::isize::MAX as usize })
+        }
+    }
+}
+
 pub struct Plan<X> {
     planner: Planner<X, Ready>,
     plan: RawPlan,
 }
 
-impl<I: MutStrided> Plan<Inplace<I>>
-    where <I as Strided>::Elem: FftData<I::Elem>
-{
+impl<I> Plan<Inplace<I>> {
     pub fn in_out(&mut self) -> &mut I {
         &mut self.planner.data.in_out
     }
@@ -434,3 +826,313 @@ impl<X> Plan<X> {
         self.plan.debug_print()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use libc::ptrdiff_t;
+    use num::complex::Complex64;
+    use rand::random;
+    use strided::MutStride;
+    use ffi;
+    use mem::{FftwVec, FftwArray};
+    use error::{Buffer, Error};
+    use super::{R2rKind, Dim, Kind, Planner, Span, buffer_requirements};
+
+    fn random_stride() -> isize {
+        let s = (random::<usize>() % 7 + 1) as isize;
+        if random() { s } else { -s }
+    }
+
+    fn random_dims(max_rank: usize) -> Vec<Dim> {
+        (0..random::<usize>() % max_rank + 1).map(|_| {
+            Dim {
+                n: random::<usize>() % 6,
+                in_stride: random_stride(),
+                out_stride: random_stride(),
+            }
+        }).collect()
+    }
+
+    /// The span reached by visiting every index of an array with the
+    /// given lengths and strides.
+    fn visit_all(ns: &[usize], strides: &[isize]) -> Span {
+        let mut idx = vec![0; ns.len()];
+        let (mut min, mut max) = (0, 0);
+        loop {
+            let offset = idx.iter().zip(strides.iter()).fold(0, |o, (&i, &s)| o + i as isize * s);
+            if offset < min { min = offset }
+            if offset > max { max = offset }
+
+            // increment the multi-index, stopping when it wraps.
+            let mut k = 0;
+            while k < idx.len() {
+                idx[k] += 1;
+                if idx[k] < ns[k] { break }
+                idx[k] = 0;
+                k += 1;
+            }
+            if k == idx.len() {
+                return Span { base: -min as usize, extent: (max - min) as usize + 1 }
+            }
+        }
+    }
+
+    fn brute_force_extent(dims: &[Dim], howmany: &[Dim], kind: Kind) -> (Span, Span) {
+        let all = dims.iter().chain(howmany.iter()).collect::<Vec<_>>();
+        let last = dims.len() - 1;
+        let (in_ns, out_ns): (Vec<_>, Vec<_>) = all.iter().enumerate().map(|(i, d)| {
+            match kind {
+                Kind::R2c if i == last => (d.n, d.n / 2 + 1),
+                Kind::C2r if i == last => (d.n / 2 + 1, d.n),
+                _ => (d.n, d.n),
+            }
+        }).unzip();
+        if in_ns.iter().chain(out_ns.iter()).any(|&n| n == 0) {
+            let empty = Span { base: 0, extent: 0 };
+            return (empty, empty)
+        }
+
+        let in_strides = all.iter().map(|d| d.in_stride).collect::<Vec<_>>();
+        let out_strides = all.iter().map(|d| d.out_stride).collect::<Vec<_>>();
+        (visit_all(&in_ns, &in_strides), visit_all(&out_ns, &out_strides))
+    }
+
+    #[test]
+    fn extent_matches_brute_force() {
+        for _ in 0..1000 {
+            let dims = random_dims(3);
+            let howmany = if random() { random_dims(2) } else { vec![] };
+            for &kind in [Kind::C2c, Kind::R2c, Kind::C2r, Kind::R2r].iter() {
+                assert_eq!(Dim::extent(&dims, &howmany, kind).unwrap(),
+                           brute_force_extent(&dims, &howmany, kind),
+                           "{:?} {:?} {:?}", dims, howmany, kind);
+            }
+        }
+    }
+
+    #[test]
+    fn subarray_lengths_are_checked() {
+        let mut in_ = FftwVec::<Complex64>::zeros(64);
+        let mut out = FftwVec::<Complex64>::zeros(64);
+        let zero = Error::InvalidArgument("Planner.nd_subarray: zero array length");
+        let short = Error::InvalidArgument("Planner.nd_subarray: array shorter than the \
+                                            transform");
+        for &(dims, err) in [(&[(4, 0, 4)][..], &zero),
+                             (&[(4, 4, 8), (2, 2, 0)][..], &zero),
+                             (&[(4, 3, 4)][..], &short),
+                             (&[(4, 4, -3)][..], &short),
+                             (&[(2, 2, 2), (4, 4, 3)][..], &short)].iter() {
+            let res = Planner::new().input(&mut in_[..]).output(&mut out[..])
+                .nd_subarray(dims).plan();
+            assert_eq!(res.err().as_ref(), Some(err), "{:?}", dims);
+        }
+
+        // the complex side of a real-to-complex transform is shorter.
+        let mut real = FftwVec::<f64>::zeros(64);
+        assert!(Planner::new().input(&mut real[..]).output(&mut out[..])
+                .nd_subarray(&[(3, 4, 4), (6, 8, 4)]).plan().is_ok());
+        assert_eq!(Planner::new().input(&mut real[..]).output(&mut out[..])
+                   .nd_subarray(&[(6, 8, 3)]).plan().err().as_ref(), Some(&short));
+    }
+
+    #[test]
+    fn subarray_bounds_are_exact() {
+        for _ in 0..100 {
+            let dims = (0..random::<usize>() % 3 + 1).map(|_| {
+                let n = random::<usize>() % 5 + 1;
+                let pad = |reverse: bool| {
+                    let len = (n + random::<usize>() % 3) as isize;
+                    if reverse { -len } else { len }
+                };
+                (n, pad(random()), pad(random()))
+            }).collect::<Vec<_>>();
+
+            // the extent of a row-major array with padded dimensions
+            // (reversing an axis doesn't change it).
+            let extent = |pick: &Fn(&(usize, isize, isize)) -> isize| {
+                let mut stride = 1;
+                let mut max = 0;
+                for d in dims.iter().rev() {
+                    max += (d.0 - 1) * stride;
+                    stride *= pick(d).abs() as usize;
+                }
+                max + 1
+            };
+            let in_len = extent(&|d| d.1);
+            let out_len = extent(&|d| d.2);
+
+            let mut in_ = FftwVec::<Complex64>::zeros(in_len);
+            let mut out = FftwVec::<Complex64>::zeros(out_len);
+            assert!(Planner::new().input(&mut in_[..]).output(&mut out[..])
+                    .nd_subarray(&dims).plan().is_ok(), "{:?}", dims);
+
+            let res = Planner::new().input(&mut in_[..in_len - 1]).output(&mut out[..])
+                .nd_subarray(&dims).plan();
+            assert_eq!(res.err(), Some(Error::BufferTooSmall { buffer: Buffer::Input,
+                                                               required: in_len,
+                                                               actual: in_len - 1 }));
+            let res = Planner::new().input(&mut in_[..]).output(&mut out[..out_len - 1])
+                .nd_subarray(&dims).plan();
+            assert_eq!(res.err(), Some(Error::BufferTooSmall { buffer: Buffer::Output,
+                                                               required: out_len,
+                                                               actual: out_len - 1 }));
+        }
+    }
+
+    #[test]
+    fn reversed_input() {
+        let n = 8;
+        let mut in_ = FftwVec::<Complex64>::zeros(n);
+        let mut out = FftwVec::<Complex64>::zeros(n);
+        let mut rev = FftwVec::<Complex64>::zeros(n);
+        let mut rev_out = FftwVec::<Complex64>::zeros(n);
+        for i in 0..n {
+            in_[i] = Complex64::new(random(), random());
+            rev[n - 1 - i] = in_[i];
+        }
+
+        // transforming a reversed view of `rev` is the same as
+        // transforming `in_`.
+        Planner::new().input(&mut in_[..]).output(&mut out[..])
+            ._1d(n).plan().unwrap().execute();
+        Planner::new().input(&mut rev[..]).output(&mut rev_out[..])
+            .nd_subarray(&[(n, -(n as isize), n as isize)]).plan().unwrap().execute();
+        for (a, b) in out.iter().zip(rev_out.iter()) {
+            assert!((*a - *b).norm() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn any_slice_container_is_a_buffer() {
+        use std::ops::{Deref, DerefMut};
+
+        // a container this crate knows nothing about.
+        struct Samples(Vec<Complex64>);
+        impl Deref for Samples {
+            type Target = [Complex64];
+            fn deref(&self) -> &[Complex64] { &self.0 }
+        }
+        impl DerefMut for Samples {
+            fn deref_mut(&mut self) -> &mut [Complex64] { &mut self.0 }
+        }
+
+        let in_ = Samples(vec![Complex64::new(1.0, 0.0); 8]);
+        let out = Samples(vec![Complex64::new(0.0, 0.0); 8]);
+        let mut plan = Planner::new().input(in_).output(out)._1d(8).plan().unwrap();
+        plan.execute();
+        assert_eq!(plan.output()[0], Complex64::new(8.0, 0.0));
+    }
+
+    #[test]
+    fn array_dims_are_inferred() {
+        let mut in_ = FftwArray::from_fn(&[4, 6], |_| Complex64::new(random(), random()));
+        let mut out = FftwArray::<Complex64>::zeros(&[4, 6]);
+        let mut expected = FftwVec::<Complex64>::zeros(24);
+
+        Planner::new().input_array(&mut in_).output_array(&mut out).plan().unwrap().execute();
+        Planner::new().input(in_.as_mut_slice()).output(&mut expected[..])
+            ._2d(4, 6).plan().unwrap().execute();
+        for (a, b) in out.as_slice().iter().zip(expected.iter()) {
+            assert!((*a - *b).norm() < 1e-10);
+        }
+
+        // the complex side of a real-to-complex transform is half the
+        // size along the last axis.
+        let mut real = FftwArray::<f64>::zeros(&[4, 6]);
+        let mut complex = FftwArray::<Complex64>::zeros(&[4, 4]);
+        assert!(Planner::new().input_array(&mut real).output_array(&mut complex).plan().is_ok());
+        assert_eq!(Planner::new().input_array(&mut real).output_array(&mut out).plan().err(),
+                   Some(Error::InvalidArgument("array shapes don't match the transform")));
+        // with only the complex side shaped, the length is ambiguous.
+        let mut real_out = FftwVec::<f64>::zeros(24);
+        assert_eq!(Planner::new().input_array(&mut complex).output(&mut real_out[..]).plan().err(),
+                   Some(Error::NoLengthNoDefault));
+    }
+
+    #[test]
+    fn requirements_match_planning() {
+        let dims = [(3, 4, 3), (5, -6, 5)];
+        let in_len = 2 * 6 + 4 * 1 + 1;
+        let out_len = 2 * 5 + 4 + 1;
+        let as_dims = [Dim { n: 3, in_stride: 6, out_stride: 5 },
+                       Dim { n: 5, in_stride: -1, out_stride: 1 }];
+        let req = buffer_requirements(Kind::C2c, &as_dims, &[], false).unwrap();
+        assert_eq!((req.input, req.output), (in_len, out_len));
+        assert!(req.alignment.is_power_of_two());
+
+        let mut in_ = FftwVec::<Complex64>::zeros(req.input);
+        let mut out = FftwVec::<Complex64>::zeros(req.output);
+        assert!(Planner::new().input(&mut in_[..]).output(&mut out[..])
+                .nd_subarray(&dims).plan().is_ok());
+        let res = Planner::new().input(&mut in_[..req.input - 1]).output(&mut out[..])
+            .nd_subarray(&dims).plan();
+        assert_eq!(res.err(), Some(Error::BufferTooSmall { buffer: Buffer::Input,
+                                                           required: req.input,
+                                                           actual: req.input - 1 }));
+
+        // in-place real-to-complex needs room for the complex output.
+        let d = [Dim { n: 8, in_stride: 1, out_stride: 1 }];
+        let req = buffer_requirements(Kind::R2c, &d, &[], true).unwrap();
+        assert_eq!((req.input, req.output), (10, 5));
+        assert_eq!(buffer_requirements(Kind::R2c, &[], &[], false).err(),
+                   Some(Error::InvalidArgument("buffer_requirements: empty dimensions")));
+    }
+
+    #[test]
+    fn iodims_are_compatible() {
+        // handle 32-bit and 64-bit platforms properly
+        let n = 0x0102_0304_0506_0708u64 as usize;
+        let is = 0x090A_0B0C_0D0E_0F00u64 as isize;
+        let os = -0x1122_3344_5566_7788i64 as isize;
+
+        let d = Dim { n: n, in_stride: is, out_stride: os };
+        let f = ffi::fftw_iodim64 { n: n as ptrdiff_t, is: is as ptrdiff_t, os: os as ptrdiff_t };
+        type T = (usize, isize, isize);
+        unsafe {
+            assert_eq!(mem::transmute::<_, T>(d), mem::transmute::<_, T>(f));
+        }
+    }
+
+    #[test]
+    fn complex_is_compatible() {
+        let c = Complex64::new(1.5, -2.5);
+        assert_eq!(mem::size_of::<Complex64>(), mem::size_of::<ffi::fftw_complex>());
+        assert_eq!(mem::align_of::<Complex64>(), mem::align_of::<ffi::fftw_complex>());
+        unsafe {
+            assert_eq!(mem::transmute::<_, ffi::fftw_complex>(c), [1.5, -2.5]);
+        }
+    }
+
+    #[test]
+    fn r2r_kinds_are_compatible() {
+        let kinds = [(R2rKind::R2ch, ffi::FFTW_R2HC), (R2rKind::Hc2r, ffi::FFTW_HC2R),
+                     (R2rKind::Dht, ffi::FFTW_DHT),
+                     (R2rKind::Dct00, ffi::FFTW_REDFT00), (R2rKind::Dct01, ffi::FFTW_REDFT01),
+                     (R2rKind::Dct10, ffi::FFTW_REDFT10), (R2rKind::Dct11, ffi::FFTW_REDFT11),
+                     (R2rKind::Dst00, ffi::FFTW_RODFT00), (R2rKind::Dst01, ffi::FFTW_RODFT01),
+                     (R2rKind::Dst10, ffi::FFTW_RODFT10), (R2rKind::Dst11, ffi::FFTW_RODFT11)];
+        for &(k, f) in kinds.iter() {
+            assert_eq!(k.as_fftw(), f);
+        }
+    }
+
+    #[test]
+    fn strided_views() {
+        let mut v = FftwVec::<Complex64>::zeros(16);
+        let (a, b) = MutStride::new(&mut v[..]).substrides2_mut();
+        assert!(Planner::new().input(a).output(b).plan().is_ok());
+    }
+
+    #[test]
+    fn overflow_reports_size() {
+        let max = ::std::isize::MAX as usize;
+        let d = [Dim { n: 3, in_stride: (max / 2 + 1) as isize, out_stride: 1 }];
+        assert_eq!(Dim::extent(&d, &[], Kind::C2c).err(),
+                   Some(Error::SizeOverflow { size: 2 * (max / 2 + 1), max: max }));
+        // too large for a `usize` too.
+        let d = [Dim { n: !0, in_stride: -2, out_stride: 1 }];
+        assert_eq!(Dim::extent(&d, &[], Kind::C2c).err(),
+                   Some(Error::SizeOverflow { size: !0, max: max }));
+    }
+}
@@ -0,0 +1,209 @@
+//! Plans that can be executed by several threads at once.
+
+use num::complex::Complex64;
+
+use plan::RawPlan;
+use error::{Buffer, Error};
+use super::{Dim, FftData, FftSpec, Kind, Plan, PlanResult, Span, alignment_of};
+
+/// A plan without buffers of its own, which transforms buffers given
+/// to each execution.
+///
+/// This is `Sync`, since FFTW guarantees that executing a plan on
+/// new arrays is thread-safe, so it can be put in an `Arc` and
+/// executed from many threads concurrently. The buffers must have the
+/// same layout, element types and SIMD alignment as those the plan
+/// was created with; an `FftwVec` always has the same alignment as
+/// any other.
+pub struct SharedPlan {
+    raw: RawPlan,
+    kind: Kind,
+    in_alignment: usize,
+    // `None` for an in-place plan.
+    out_alignment: Option<usize>,
+    in_span: Span,
+    out_span: Span,
+}
+
+// new-array execution through `&self` is the only way the plan is
+// used.
+unsafe impl Sync for SharedPlan {}
+
+impl<X: FftSpec> Plan<X> {
+    /// Drop the buffers of this plan, keeping the plan itself to
+    /// execute on others.
+    pub fn into_shared(self) -> SharedPlan {
+        let Plan { mut planner, plan } = self;
+        let (in_alignment, out_alignment, default) = planner.data.layout();
+        let dims = if planner.meta.dims.is_empty() {
+            vec![default]
+        } else {
+            planner.meta.dims.clone()
+        };
+        // this was checked when planning.
+        let (in_span, out_span) = Dim::extent(&dims, &planner.meta.howmany, X::kind()).unwrap();
+        SharedPlan {
+            raw: plan,
+            kind: X::kind(),
+            in_alignment: in_alignment,
+            out_alignment: out_alignment,
+            in_span: in_span,
+            out_span: out_span,
+        }
+    }
+}
+
+impl SharedPlan {
+    /// The type of transform this performs.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+    /// Whether this transforms a single buffer in place.
+    pub fn is_inplace(&self) -> bool {
+        self.out_alignment.is_none()
+    }
+    /// The minimum number of elements of the input and output
+    /// buffers.
+    pub fn lens(&self) -> (usize, usize) {
+        (self.in_span.extent, self.out_span.extent)
+    }
+
+    /// Transform `in_` into `out`.
+    pub fn execute<T: FftData<U>, U>(&self, in_: &mut [T], out: &mut [U]) -> PlanResult<()> {
+        try!(self.check_kind(<T as FftData<U>>::kind()));
+        let alignments = (alignment_of(in_.as_ptr()), Some(alignment_of(out.as_ptr())));
+        if self.out_alignment.is_none() {
+            return Err(Error::InvalidArgument("SharedPlan: executing an in-place plan \
+                                               out-of-place"))
+        }
+        try!(self.check_alignment(alignments));
+        unsafe {
+            let in_ptr = try!(self.in_span.locate(Buffer::Input, in_.as_mut_ptr(), in_.len(), 1));
+            let out_ptr = try!(self.out_span.locate(Buffer::Output, out.as_mut_ptr(),
+                                                    out.len(), 1));
+            self.execute_raw(in_ptr, out_ptr);
+        }
+        Ok(())
+    }
+
+    /// Transform `buf` in place.
+    ///
+    /// In-place real-to-complex and complex-to-real plans can't be
+    /// executed this way, since the element type changes.
+    pub fn execute_inplace<T: FftData<T>>(&self, buf: &mut [T]) -> PlanResult<()> {
+        try!(self.check_kind(<T as FftData<T>>::kind()));
+        if self.out_alignment.is_some() {
+            return Err(Error::InvalidArgument("SharedPlan: executing an out-of-place plan \
+                                               in-place"))
+        }
+        try!(self.check_alignment((alignment_of(buf.as_ptr()), None)));
+        unsafe {
+            let ptr = buf.as_mut_ptr();
+            let in_ptr = try!(self.in_span.locate(Buffer::Input, ptr, buf.len(), 1));
+            let out_ptr = try!(self.out_span.locate(Buffer::Output, ptr, buf.len(), 1));
+            self.execute_raw(in_ptr, out_ptr);
+        }
+        Ok(())
+    }
+
+    fn check_kind(&self, kind: Kind) -> PlanResult<()> {
+        if kind == self.kind {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument("SharedPlan: element types don't match the plan"))
+        }
+    }
+
+    fn check_alignment(&self, (in_, out): (usize, Option<usize>)) -> PlanResult<()> {
+        if in_ == self.in_alignment && out == self.out_alignment {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument("SharedPlan: buffer alignment doesn't match the plan"))
+        }
+    }
+
+    /// Execute with pointers of the types the plan's kind requires.
+    unsafe fn execute_raw<T, U>(&self, in_: *mut T, out: *mut U) {
+        match self.kind {
+            Kind::C2c => self.raw.execute_dft(in_ as *mut Complex64, out as *mut Complex64),
+            Kind::R2c => self.raw.execute_dft_r2c(in_ as *mut f64, out as *mut Complex64),
+            Kind::C2r => self.raw.execute_dft_c2r(in_ as *mut Complex64, out as *mut f64),
+            Kind::R2r => self.raw.execute_r2r(in_ as *mut f64, out as *mut f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use num::complex::Complex64;
+    use mem::FftwVec;
+    use error::{Buffer, Error};
+    use super::super::{Direction, Planner};
+    use super::SharedPlan;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn plans_are_send() {
+        assert_send::<::plan::RawPlan>();
+        assert_send::<::plan::Plan<FftwVec<f64>, FftwVec<Complex64>>>();
+        assert_send::<super::super::Plan<super::super::Io<FftwVec<f64>, FftwVec<f64>>>>();
+
+        let mut plan = Planner::new().input(FftwVec::<Complex64>::zeros(4))
+            .output(FftwVec::<Complex64>::zeros(4)).plan().unwrap();
+        plan.input()[0] = Complex64::new(1.0, 0.0);
+        let out = thread::spawn(move || {
+            plan.execute();
+            plan.output()[3]
+        }).join().unwrap();
+        assert!((out - Complex64::new(1.0, 0.0)).norm() < 1e-10);
+    }
+
+    #[test]
+    fn concurrent_execution() {
+        let plan = Planner::new().direction(Direction::Backward)
+            .input(FftwVec::<Complex64>::zeros(64)).output(FftwVec::<Complex64>::zeros(64))
+            .plan().unwrap();
+        let plan: Arc<SharedPlan> = Arc::new(plan.into_shared());
+        assert_eq!(plan.lens(), (64, 64));
+
+        let threads = (0..8).map(|i| {
+            let plan = plan.clone();
+            thread::spawn(move || {
+                let mut in_ = FftwVec::<Complex64>::zeros(64);
+                let mut out = FftwVec::<Complex64>::zeros(64);
+                for _ in 0..100 {
+                    in_[0] = Complex64::new(i as f64, 0.0);
+                    plan.execute(&mut in_, &mut out).unwrap();
+                    assert!(out.iter().all(|x| (*x - in_[0]).norm() < 1e-10));
+                }
+            })
+        }).collect::<Vec<_>>();
+        for t in threads {
+            t.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn invalid_buffers() {
+        let plan = Planner::new().input(FftwVec::<f64>::zeros(8))
+            .output(FftwVec::<Complex64>::zeros(5))._1d(8).plan().unwrap().into_shared();
+        let mut in_ = FftwVec::<f64>::zeros(8);
+        let mut out = FftwVec::<Complex64>::zeros(5);
+        assert!(plan.execute(&mut in_, &mut out).is_ok());
+
+        assert_eq!(plan.execute(&mut in_[..7], &mut out).err(),
+                   Some(Error::BufferTooSmall { buffer: Buffer::Input, required: 8, actual: 7 }));
+        let mut c = FftwVec::<Complex64>::zeros(8);
+        assert!(plan.execute(&mut c, &mut out).is_err());
+        assert!(plan.execute_inplace(&mut c).is_err());
+
+        // misaligned buffers would crash FFTW's SIMD code.
+        let mut long = FftwVec::<f64>::zeros(9);
+        if ::mem::simd_alignment() > 8 {
+            assert!(plan.execute(&mut long[1..], &mut out).is_err());
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Planning the same transform for many buffers.
+
+use std::marker::PhantomData;
+use libc::c_uint;
+use strided::MutStrided;
+
+use error::Error;
+use threading::Threads;
+use super::{Dim, Direction, FftSpec, Inplace, Io, Kind, Meta, Plan, Planner, PlanResult,
+            R2rKind, Ready, Rigor, Shaped};
+
+/// The configuration of a transform, without any buffers.
+///
+/// Unlike a `Planner`, this isn't consumed by planning: `plan_for`
+/// can be called with any number of compatible buffers, and
+/// templates can be compared or used as keys, e.g. to look up an
+/// existing plan.
+///
+/// If no dimensions are set, they are inferred from the arrays given
+/// to `plan_for_arrays` or `plan_inplace_for_array`, as with a
+/// `Planner`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Template {
+    rigor: Rigor,
+    wisdom_restriction: bool,
+    direction: Option<Direction>,
+    flags: c_uint,
+    threads: Threads,
+    dims: Vec<Dim>,
+    howmany: Vec<Dim>,
+    r2r_kinds: Vec<R2rKind>,
+    // reported when planning, as with a `Planner`.
+    error: Option<&'static str>,
+}
+
+impl Template {
+    /// A template with the same defaults as `Planner::new`.
+    pub fn new() -> Template {
+        Template {
+            rigor: Rigor::Estimate,
+            wisdom_restriction: false,
+            direction: None,
+            flags: 0,
+            threads: Threads::Fixed(1),
+            dims: vec![],
+            howmany: vec![],
+            r2r_kinds: vec![],
+            error: None,
+        }
+    }
+
+    /// Set the rigor to use for plans.
+    pub fn rigor(mut self, r: Rigor) -> Template {
+        self.rigor = r;
+        self
+    }
+    /// Set whether planning should only succeed if there exists
+    /// wisdom created with at least the rigor level set.
+    pub fn wisdom_restriction(mut self, wisdom_only: bool) -> Template {
+        self.wisdom_restriction = wisdom_only;
+        self
+    }
+    /// Set the direction of the transform, as for `Planner::direction`.
+    pub fn direction(mut self, direction: Direction) -> Template {
+        self.direction = Some(direction);
+        self
+    }
+    /// Set extra FFTW flags, e.g. `FFTW_DESTROY_INPUT`.
+    pub fn flags(mut self, flags: c_uint) -> Template {
+        self.flags = flags;
+        self
+    }
+
+    /// Set how many threads plans use.
+    pub fn threads(mut self, threads: Threads) -> Template {
+        self.threads = threads;
+        self
+    }
+
+    pub fn _1d(self, n: usize) -> Template {
+        self.nd(&[n])
+    }
+    pub fn _2d(self, n0: usize, n1: usize) -> Template {
+        self.nd(&[n0, n1])
+    }
+    pub fn _3d(self, n0: usize, n1: usize, n2: usize) -> Template {
+        self.nd(&[n0, n1, n2])
+    }
+
+    /// Transform a contiguous row-major array with the given
+    /// dimensions.
+    pub fn nd(mut self, dims: &[usize]) -> Template {
+        if dims.is_empty() {
+            self.error = Some("Template.nd: empty dimensions");
+        }
+        self.dims = dims.iter().map(|&n| Dim { n: n, in_stride: 0, out_stride: 0 }).collect();
+        let mut stride = 1;
+        for place in self.dims.iter_mut().rev() {
+            place.in_stride = stride;
+            place.out_stride = stride;
+            stride *= place.n as isize;
+        }
+        self
+    }
+    /// Use arbitrary dimensions and strides, as for
+    /// `Planner::nd_strided`.
+    pub fn nd_strided(mut self, dims: &[Dim]) -> Template {
+        if dims.is_empty() {
+            self.error = Some("Template.nd_strided: empty dimensions");
+        }
+        self.dims = dims.to_vec();
+        self
+    }
+    /// Repeat the transform over the given dimensions, with strides
+    /// giving the distance between each transform, in elements of
+    /// the buffers (like the strides of the dimensions).
+    pub fn howmany(mut self, dims: &[Dim]) -> Template {
+        self.howmany = dims.to_vec();
+        self
+    }
+    /// Set the kinds of a real-to-real transform: either one kind for
+    /// all dimensions, or one per dimension.
+    pub fn r2r_kinds(mut self, kinds: &[R2rKind]) -> Template {
+        self.r2r_kinds = kinds.to_vec();
+        self
+    }
+
+    /// The dimensions of the transform, which are empty if they are
+    /// to be inferred from the buffers.
+    pub fn dims(&self) -> &[Dim] {
+        &self.dims
+    }
+    /// The dimensions the transform is repeated over.
+    pub fn howmany_dims(&self) -> &[Dim] {
+        &self.howmany
+    }
+
+    /// Plan this transform from `in_` to `out`.
+    pub fn plan_for<I, O>(&self, in_: I, out: O) -> PlanResult<Plan<Io<I, O>>>
+        where I: MutStrided, O: MutStrided, Io<I, O>: FftSpec
+    {
+        self.plan_io(in_, out, None, None)
+    }
+
+    /// Plan this transform from the array `in_` to the array `out`,
+    /// inferring the dimensions from their shapes if none were set.
+    pub fn plan_for_arrays<I, O>(&self, in_: I, out: O) -> PlanResult<Plan<Io<I, O>>>
+        where I: MutStrided + Shaped, O: MutStrided + Shaped, Io<I, O>: FftSpec
+    {
+        let (in_shape, out_shape) = (in_.shape().to_vec(), out.shape().to_vec());
+        self.plan_io(in_, out, Some(in_shape), Some(out_shape))
+    }
+
+    /// Plan this transform in place, in `in_out`.
+    pub fn plan_inplace_for<I>(&self, in_out: I) -> PlanResult<Plan<Inplace<I>>>
+        where I: MutStrided, Inplace<I>: FftSpec
+    {
+        self.plan_inplace(in_out, None)
+    }
+
+    /// Plan this transform in place, in the array `in_out`, inferring
+    /// the dimensions from its shape if none were set.
+    pub fn plan_inplace_for_array<I>(&self, in_out: I) -> PlanResult<Plan<Inplace<I>>>
+        where I: MutStrided + Shaped, Inplace<I>: FftSpec
+    {
+        let shape = in_out.shape().to_vec();
+        self.plan_inplace(in_out, Some(shape))
+    }
+
+    fn plan_io<I, O>(&self, in_: I, out: O,
+                     in_shape: Option<Vec<usize>>, out_shape: Option<Vec<usize>>)
+                     -> PlanResult<Plan<Io<I, O>>>
+        where I: MutStrided, O: MutStrided, Io<I, O>: FftSpec
+    {
+        let mut meta = self.meta();
+        meta.in_stride = in_.stride();
+        meta.in_shape = in_shape;
+        meta.out_stride = out.stride();
+        meta.out_shape = out_shape;
+        try!(self.check_kinds(<Io<I, O> as FftSpec>::kind()));
+
+        Planner {
+            meta: meta,
+            data: Io { in_: in_, out: out },
+            _marker: PhantomData::<Ready>
+        }.plan()
+    }
+
+    fn plan_inplace<I>(&self, in_out: I, shape: Option<Vec<usize>>)
+                       -> PlanResult<Plan<Inplace<I>>>
+        where I: MutStrided, Inplace<I>: FftSpec
+    {
+        let mut meta = self.meta();
+        meta.in_stride = in_out.stride();
+        meta.out_stride = meta.in_stride;
+        meta.in_shape = shape;
+        meta.out_shape = meta.in_shape.clone();
+        try!(self.check_kinds(<Inplace<I> as FftSpec>::kind()));
+
+        Planner {
+            meta: meta,
+            data: Inplace { in_out: in_out },
+            _marker: PhantomData::<Ready>
+        }.plan()
+    }
+
+    fn meta(&self) -> Meta {
+        let mut meta = Planner::new().meta;
+        meta.rigor = self.rigor;
+        meta.wisdom_restriction = self.wisdom_restriction;
+        meta.direction = self.direction;
+        meta.flags = self.flags;
+        meta.threads = self.threads;
+        meta.dims = self.dims.clone();
+        meta.howmany = self.howmany.clone();
+        meta.r2r_kinds = self.r2r_kinds.iter().map(|k| k.as_fftw()).collect();
+        meta.error = self.error.map(Error::InvalidArgument);
+        meta
+    }
+
+    /// Real-to-real transforms need kinds, and nothing else takes
+    /// them.
+    fn check_kinds(&self, kind: Kind) -> PlanResult<()> {
+        match (kind, self.r2r_kinds.is_empty()) {
+            (Kind::R2r, true) => Err(Error::InvalidArgument("Template: no r2r kinds")),
+            (Kind::R2r, false) | (_, true) => Ok(()),
+            (_, false) => Err(Error::InvalidArgument("Template: r2r kinds for a transform \
+                                                      that isn't real-to-real")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use num::complex::Complex64;
+    use strided::MutStride;
+    use mem::{FftwVec, FftwArray};
+    use error::Error;
+    use super::super::{Dim, Direction, R2rKind, Rigor};
+    use super::Template;
+
+    #[test]
+    fn plan_many_buffers() {
+        let template = Template::new().direction(Direction::Backward)._2d(2, 4);
+        for i in 0..3 {
+            let mut in_ = FftwVec::<Complex64>::zeros(8);
+            let mut out = FftwVec::<Complex64>::zeros(8);
+            in_[0] = Complex64::new(i as f64, 0.0);
+            template.plan_for(&mut in_[..], &mut out[..]).unwrap().execute();
+            assert!(out.iter().all(|x| (*x - in_[0]).norm() < 1e-10));
+        }
+
+        // dimensions come from the arrays when not set.
+        let mut a = FftwArray::<f64>::zeros(&[3, 4]);
+        let mut b = FftwArray::<Complex64>::zeros(&[3, 3]);
+        assert!(Template::new().plan_for_arrays(&mut a, &mut b).is_ok());
+        let mut c = FftwVec::<Complex64>::zeros(6);
+        assert!(Template::new()._1d(6).plan_inplace_for(&mut c[..]).is_ok());
+
+        assert_eq!(Template::new().nd(&[]).plan_inplace_for(&mut c[..]).err(),
+                   Some(Error::InvalidArgument("Template.nd: empty dimensions")));
+    }
+
+    #[test]
+    fn howmany_strided() {
+        // two transforms of length 4, of every other element.
+        let mut in_ = FftwVec::<Complex64>::zeros(16);
+        in_[8] = Complex64::new(1.0, 0.0);
+        let mut out = FftwVec::<Complex64>::zeros(8);
+        {
+            let even = MutStride::new(&mut in_[..]).substrides2_mut().0;
+            let howmany = [Dim { n: 2, in_stride: 4, out_stride: 4 }];
+            Template::new()._1d(4).howmany(&howmany)
+                .plan_for(even, &mut out[..]).unwrap().execute();
+        }
+        assert!(out[..4].iter().all(|x| x.norm() < 1e-10));
+        assert!(out[4..].iter().all(|x| (*x - Complex64::new(1.0, 0.0)).norm() < 1e-10));
+    }
+
+    #[test]
+    fn r2r_kinds() {
+        let mut in_ = FftwVec::<f64>::zeros(8);
+        let mut out = FftwVec::<f64>::zeros(8);
+        assert_eq!(Template::new().plan_for(&mut in_[..], &mut out[..]).err(),
+                   Some(Error::InvalidArgument("Template: no r2r kinds")));
+        let dct = Template::new().r2r_kinds(&[R2rKind::Dct10]);
+        assert!(dct.plan_for(&mut in_[..], &mut out[..]).is_ok());
+
+        let mut c = FftwVec::<Complex64>::zeros(8);
+        assert!(dct.plan_for(&mut in_[..], &mut c[..]).is_err());
+    }
+
+    #[test]
+    fn comparable() {
+        let a = Template::new().rigor(Rigor::Measure)._1d(16);
+        let b = Template::new()._1d(16).rigor(Rigor::Measure);
+        assert_eq!(a, b);
+        assert!(a != a.clone().direction(Direction::Forward));
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        assert!(set.contains(&b));
+        assert!(!set.contains(&Template::new()._2d(4, 4)));
+    }
+}
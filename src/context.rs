@@ -0,0 +1,153 @@
+//! FFTW keeps global state (the planner's internal tables and any
+//! accumulated wisdom) that can only be released by `fftw_cleanup`,
+//! and doing so invalidates every plan that still exists. This module
+//! counts the live plans so that the clean-up is only ever performed
+//! when it is safe.
+//!
+//! Note that cleaning up also forgets all wisdom, so export anything
+//! that should be kept (e.g. with `wisdom::export_to_file`) first.
+//!
+//! FFTW keeps separate state for each precision, but this crate only
+//! ever creates double-precision plans, so a single count is the
+//! count for that precision, and only its state is cleaned up. If
+//! FFTW's threads library has been initialised, it is cleaned up too,
+//! and initialised again by the next multi-threaded plan.
+
+use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering,
+                        ATOMIC_USIZE_INIT, ATOMIC_BOOL_INIT};
+use {ffi, lock};
+
+static LIVE_PLANS: AtomicUsize = ATOMIC_USIZE_INIT;
+static CLEANUP_PENDING: AtomicBool = ATOMIC_BOOL_INIT;
+static AUTO_CLEANUP: AtomicBool = ATOMIC_BOOL_INIT;
+static THREADS_INITIALISED: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// The number of plans that currently exist.
+pub fn live_plans() -> usize {
+    LIVE_PLANS.load(Ordering::SeqCst)
+}
+
+/// Release FFTW's internal memory, as soon as it is safe to do so.
+///
+/// If no plans exist this happens immediately and `true` is
+/// returned. Otherwise the clean-up is deferred until the last live
+/// plan is dropped, and `false` is returned.
+pub fn cleanup() -> bool {
+    lock::run(|| {
+        if LIVE_PLANS.load(Ordering::SeqCst) == 0 {
+            release();
+            CLEANUP_PENDING.store(false, Ordering::SeqCst);
+            true
+        } else {
+            CLEANUP_PENDING.store(true, Ordering::SeqCst);
+            false
+        }
+    })
+}
+
+/// Release FFTW's internal memory immediately, failing with the
+/// number of live plans if there are any.
+pub fn try_cleanup() -> Result<(), usize> {
+    lock::run(|| {
+        match LIVE_PLANS.load(Ordering::SeqCst) {
+            0 => {
+                release();
+                CLEANUP_PENDING.store(false, Ordering::SeqCst);
+                Ok(())
+            }
+            n => Err(n)
+        }
+    })
+}
+
+/// Set whether FFTW's internal memory should be released every time
+/// the last live plan is dropped.
+///
+/// This is useful for long-running processes that move between
+/// workloads, but means that every "first" plan starts from scratch
+/// (without wisdom), so it is disabled by default.
+pub fn set_auto_cleanup(auto: bool) {
+    AUTO_CLEANUP.store(auto, Ordering::SeqCst);
+}
+
+/// Record the creation of a plan.
+#[doc(hidden)]
+pub fn plan_created() {
+    LIVE_PLANS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Record the destruction of a plan, cleaning up if this was the last
+/// one and that has been requested.
+///
+/// This must be called with the FFTW lock held.
+#[doc(hidden)]
+pub fn plan_destroyed() {
+    let prev = LIVE_PLANS.fetch_sub(1, Ordering::SeqCst);
+    debug_assert!(prev > 0, "context::plan_destroyed: no live plans");
+
+    if prev == 1 && (CLEANUP_PENDING.swap(false, Ordering::SeqCst) ||
+                     AUTO_CLEANUP.load(Ordering::SeqCst)) {
+        debug!("last plan destroyed, cleaning up");
+        release();
+    }
+}
+
+/// Initialise FFTW's threads library, unless it already has been
+/// since the last clean-up.
+///
+/// This must be called with the FFTW lock held.
+#[doc(hidden)]
+#[cfg(any(feature = "threads", feature = "omp"))]
+pub fn init_threads() {
+    if !THREADS_INITIALISED.swap(true, Ordering::SeqCst) {
+        unsafe {ffi::fftw_init_threads();}
+    }
+}
+
+/// Release all of FFTW's internal memory, including that of the
+/// threads library if it was initialised.
+///
+/// This must be called with the FFTW lock held.
+fn release() {
+    if THREADS_INITIALISED.swap(false, Ordering::SeqCst) {
+        cleanup_threads();
+    }
+    unsafe {ffi::fftw_cleanup()}
+}
+
+#[cfg(any(feature = "threads", feature = "omp"))]
+fn cleanup_threads() {
+    unsafe {ffi::fftw_cleanup_threads()}
+}
+#[cfg(not(any(feature = "threads", feature = "omp")))]
+fn cleanup_threads() {}
+
+#[cfg(test)]
+mod tests {
+    use num::complex::Complex64;
+    use mem::FftwVec;
+    use builder2::Planner;
+    use threading::Threads;
+    use plan::Plan;
+    use super::{live_plans, try_cleanup};
+
+    #[test]
+    fn cleanup_refused_with_live_plan() {
+        let p = Plan::r2c_1d(8).unwrap();
+        assert!(live_plans() >= 1);
+        assert!(try_cleanup().is_err());
+        drop(p);
+    }
+
+    #[test]
+    fn threads_after_cleanup() {
+        let plan = || Planner::new().threads(Threads::Fixed(2))
+            .input(FftwVec::<Complex64>::zeros(64)).output(FftwVec::<Complex64>::zeros(64))
+            .plan();
+        drop(plan());
+        // other tests' plans may still be alive, in which case this
+        // just checks that planning keeps working.
+        let _ = try_cleanup();
+        assert_eq!(plan().is_ok(), ::threading::available());
+    }
+}
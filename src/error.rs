@@ -0,0 +1,110 @@
+//! The error type for planning.
+
+use std::error;
+use std::fmt;
+
+/// Which of the buffers of a transform an error refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffer {
+    Input,
+    Output,
+}
+
+/// The ways in which creating a plan can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A buffer has fewer elements than the transform requires.
+    BufferTooSmall {
+        buffer: Buffer,
+        required: usize,
+        actual: usize,
+    },
+    /// A size or stride is too large to be passed to FFTW, which
+    /// takes `int`s for the simple interfaces and `ptrdiff_t`s for
+    /// the guru interface.
+    ///
+    /// `size` is the value that is too large, saturated at
+    /// `usize::MAX` if it doesn't even fit in a `usize`.
+    SizeOverflow {
+        size: usize,
+        max: usize,
+    },
+    /// The transform is not supported with the options given, e.g. a
+    /// backward real-to-complex transform.
+    Unsupported(&'static str),
+    /// An argument was invalid, e.g. an empty list of dimensions.
+    InvalidArgument(&'static str),
+    /// No length was given, and the transform has no sensible default.
+    NoLengthNoDefault,
+    /// Planning was restricted to existing wisdom, and there was none
+    /// for this transform.
+    WisdomMissing,
+    /// FFTW returned a `NULL` plan.
+    FftwError,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::BufferTooSmall { buffer, required, actual } => {
+                write!(f, "{} buffer too small: requires at least {} elements, but has {}",
+                       match buffer { Buffer::Input => "input", Buffer::Output => "output" },
+                       required, actual)
+            }
+            Error::SizeOverflow { size, max } => {
+                write!(f, "size {} is larger than the maximum supported by FFTW ({})", size, max)
+            }
+            Error::Unsupported(what) => write!(f, "unsupported transform: {}", what),
+            Error::InvalidArgument(what) => write!(f, "invalid argument: {}", what),
+            _ => f.write_str(error::Error::description(self)),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::BufferTooSmall { .. } => "buffer too small",
+            Error::SizeOverflow { .. } => "size too large for FFTW",
+            Error::Unsupported(_) => "unsupported transform",
+            Error::InvalidArgument(_) => "invalid argument",
+            Error::NoLengthNoDefault => "no length given, and there is no default",
+            Error::WisdomMissing => "no wisdom available for this transform",
+            Error::FftwError => "FFTW failed to create a plan",
+        }
+    }
+}
+
+/// The result of planning.
+pub type PlanResult<T> = Result<T, Error>;
+
+/// The largest size that can be passed to FFTW's `int` interfaces.
+pub const MAX_INT: usize = 0x7F_FF_FF_FF;
+
+/// Check that `n` fits in a C `int`.
+pub fn check_int(n: usize) -> PlanResult<()> {
+    if n > MAX_INT {
+        Err(Error::SizeOverflow { size: n, max: MAX_INT })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that `n` fits in a `ptrdiff_t`.
+pub fn check_isize(n: usize) -> PlanResult<()> {
+    let max = ::std::isize::MAX as usize;
+    if n > max {
+        Err(Error::SizeOverflow { size: n, max: max })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that `buffer` has at least `required` elements.
+pub fn check_len(buffer: Buffer, required: usize, actual: usize) -> PlanResult<()> {
+    if actual < required {
+        Err(Error::BufferTooSmall { buffer: buffer, required: required, actual: actual })
+    } else {
+        Ok(())
+    }
+}
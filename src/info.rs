@@ -0,0 +1,97 @@
+//! Information about the FFTW library in use.
+
+use std::ffi::CStr;
+use std::fmt;
+use std::str;
+use libc::c_char;
+use ffi;
+
+/// The version and build configuration of the linked FFTW.
+///
+/// This is useful for logging, and for keying saved wisdom, since
+/// wisdom is only valid for the exact configuration that created it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Info {
+    /// The version string, e.g. `fftw-3.3.4-sse2-avx`.
+    pub version: &'static str,
+    /// The compiler (and flags) used to build FFTW.
+    pub cc: &'static str,
+    /// The SIMD optimisations used for the codelets, e.g. `sse2_avx`.
+    pub codelet_optim: &'static str,
+
+    /// Whether the double precision library is linked.
+    pub double: bool,
+    /// Whether the single precision library is linked.
+    pub single: bool,
+    /// Whether the long double precision library is linked.
+    pub long: bool,
+    /// Whether the quad precision library is linked.
+    pub quad: bool,
+    /// Whether the threads library is linked.
+    pub threads: bool,
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}; codelets: {})", self.version, self.cc, self.codelet_optim)
+    }
+}
+
+unsafe fn static_str(p: *const c_char) -> &'static str {
+    str::from_utf8(CStr::from_ptr(p).to_bytes()).unwrap_or("<invalid utf8>")
+}
+
+/// Retrieve information about the linked FFTW library.
+#[cfg(not(feature = "dynamic"))]
+pub fn info() -> Info {
+    unsafe {
+        Info {
+            version: static_str(ffi::fftw_version.as_ptr()),
+            cc: static_str(ffi::fftw_cc.as_ptr()),
+            codelet_optim: static_str(ffi::fftw_codelet_optim.as_ptr()),
+
+            double: ffi::LINKED_DOUBLE,
+            single: ffi::LINKED_SINGLE,
+            long: ffi::LINKED_LONG,
+            quad: ffi::LINKED_QUAD,
+            threads: ffi::LINKED_THREADS,
+        }
+    }
+}
+
+/// Retrieve information about the FFTW library loaded at runtime
+/// (loading the default one for double precision, if necessary).
+#[cfg(feature = "dynamic")]
+pub fn info() -> Info {
+    use ffi::dynamic::{self, Precision};
+
+    fn string(name: &str) -> &'static str {
+        dynamic::string(name)
+            .map(|s| unsafe {static_str(s.as_ptr())})
+            .unwrap_or("<unknown>")
+    }
+
+    Info {
+        version: string("fftw_version"),
+        cc: string("fftw_cc"),
+        codelet_optim: string("fftw_codelet_optim"),
+
+        double: dynamic::is_loaded(Precision::Double),
+        single: dynamic::is_loaded(Precision::Single),
+        long: dynamic::is_loaded(Precision::Long),
+        quad: dynamic::is_loaded(Precision::Quad),
+        threads: dynamic::has_threads(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::info;
+
+    #[test]
+    fn version() {
+        let i = info();
+        assert!(i.version.starts_with("fftw-3"), "unexpected version {}", i.version);
+        assert!(i.double);
+    }
+}
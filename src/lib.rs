@@ -1,32 +1,44 @@
 #![crate_type="lib"]
-#![feature(raw, convert, static_mutex)]
+#![feature(raw, convert, static_mutex, catch_panic)]
 
 #[macro_use]
 extern crate log;
 extern crate libc;
 extern crate num;
 extern crate strided;
+extern crate scoped_threadpool;
+#[cfg(test)]
+extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 
 extern crate fftw3_sys as ffi;
 
-pub use mem::FftwVec;
-pub use plan::{Plan, RawPlan};
-pub use builder2::Planner;
+pub use mem::{FftwVec, FftwBuffer, FftwArray, UninitFftwVec};
+pub use plan::{Plan, PlanOptions, RawPlan};
+pub use builder2::{Planner, Template, SharedPlan, Rigor, Direction};
+pub use info::{info, Info};
+pub use error::{Error, Buffer, PlanResult};
 
 mod plan;
-mod mem;
+mod info;
+mod error;
 
+pub mod mem;
 pub mod builder;
 pub mod builder2;
 
 pub mod wisdom;
 pub mod lock;
+pub mod context;
+pub mod threading;
+pub mod batch;
 
 pub mod traits;
 
 #[test]
 fn test() {
-    let mut p = plan::Plan::r2c_1d(4);
+    let mut p = plan::Plan::r2c_1d(4).unwrap();
     p.input()[0] = 1.0;
     p.input()[1] = 1.0;
 
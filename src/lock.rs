@@ -1,8 +1,16 @@
 //! Some functions in FFTW are not thread-safe, and one should ensure
 //! that only one thread is executing these at a time. This module
-//! provides a lock for this purpose.
+//! provides a lock for this purpose, along with hooks for observing
+//! (or synchronising) FFTW's planner.
 
+use std::cell::Cell;
 use std::sync::{StaticMutex, MUTEX_INIT};
+#[cfg(feature = "hooks")]
+use std::{mem, thread};
+#[cfg(feature = "hooks")]
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+#[cfg(feature = "hooks")]
+use ffi;
 
 /// Hold this lock when doing anything thread-unsafe with FFTW.
 pub static LOCK: StaticMutex = MUTEX_INIT;
@@ -13,3 +21,114 @@ pub fn run<A, F: FnOnce() -> A>(f: F) -> A {
     let _g = LOCK.lock();
     f()
 }
+
+/// Functions to run before and after every planning operation.
+#[cfg(feature = "hooks")]
+struct Hooks {
+    before: Box<Fn() + Send + Sync>,
+    after: Box<Fn() + Send + Sync>,
+}
+
+#[cfg(feature = "hooks")]
+static HOOKS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+thread_local!(static HOOK_PANICKED: Cell<bool> = Cell::new(false));
+
+/// Register functions to be called before and after every planning
+/// operation (including those performed by FFTW internally, e.g. in
+/// the `wisdom` module), replacing any existing ones.
+///
+/// This is useful for timing planning, logging, or taking a lock of
+/// one's own. If either panics, the panic is caught at the FFI
+/// boundary and a new one is raised once FFTW has returned.
+///
+/// This requires FFTW 3.3.5 or later, and so is only available with
+/// the `hooks` feature.
+///
+/// The hooks are run with `LOCK` held, so they must not plan, or
+/// create or drop a `RawPlan` or `Plan` (or do anything else that
+/// takes the lock): doing so deadlocks.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// fftw3::lock::set_planner_hooks(|| debug!("planning..."), || debug!("planned"));
+/// ```
+#[cfg(feature = "hooks")]
+pub fn set_planner_hooks<B, A>(before: B, after: A)
+    where B: Fn() + Send + Sync + 'static, A: Fn() + Send + Sync + 'static
+{
+    let hooks = Box::new(Hooks { before: Box::new(before), after: Box::new(after) });
+    replace_hooks(unsafe {mem::transmute::<Box<Hooks>, usize>(hooks)});
+}
+
+/// Remove the functions registered by `set_planner_hooks`.
+#[cfg(feature = "hooks")]
+pub fn clear_planner_hooks() {
+    replace_hooks(0)
+}
+
+#[cfg(feature = "hooks")]
+fn replace_hooks(new: usize) {
+    run(|| {
+        let old = HOOKS.swap(new, Ordering::SeqCst);
+        unsafe {
+            if new == 0 {
+                ffi::fftw_set_planner_hooks(None, None)
+            } else {
+                ffi::fftw_set_planner_hooks(Some(before_planner), Some(after_planner))
+            }
+            // no planning can be happening (we hold the lock), so the
+            // old hooks are not in use.
+            if old != 0 {
+                drop(mem::transmute::<usize, Box<Hooks>>(old))
+            }
+        }
+    })
+}
+
+#[cfg(feature = "hooks")]
+extern "C" fn before_planner() {
+    call_hook(|h| &*h.before)
+}
+#[cfg(feature = "hooks")]
+extern "C" fn after_planner() {
+    call_hook(|h| &*h.after)
+}
+
+#[cfg(feature = "hooks")]
+fn call_hook<F>(which: F) where F: FnOnce(&'static Hooks) -> &'static (Fn() + Send + Sync) {
+    let hooks = HOOKS.load(Ordering::SeqCst);
+    if hooks == 0 {
+        return
+    }
+    let f = which(unsafe {&*(hooks as *const Hooks)});
+
+    // unwinding into C is undefined behaviour, so stash the panic
+    // for `check_hook_panic` to rethrow.
+    if thread::catch_panic(move || f()).is_err() {
+        HOOK_PANICKED.with(|p| p.set(true))
+    }
+}
+
+/// Panic if a planner hook panicked on this thread since the last
+/// call.
+///
+/// This should be called after any FFTW function that may run the
+/// planner returns, including wisdom import and export.
+pub fn check_hook_panic() {
+    if take_hook_panic() {
+        panic!("lock: a planner hook panicked")
+    }
+}
+
+/// Whether a planner hook panicked on this thread since the last
+/// call, forgetting it if so.
+#[doc(hidden)]
+pub fn take_hook_panic() -> bool {
+    HOOK_PANICKED.with(|p| {
+        let panicked = p.get();
+        p.set(false);
+        panicked
+    })
+}
@@ -0,0 +1,642 @@
+//! N-dimensional arrays in FFTW-aligned memory.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut, Range};
+use strided::{Strided, MutStrided, Stride, MutStride};
+use traits::Zero;
+
+use super::FftwVec;
+
+/// A contiguous row-major N-dimensional array, allocated using FFTW's
+/// allocator.
+///
+/// This can be given directly to `builder2::Planner::input` and
+/// `output`, in which case the dimensions of the transform are
+/// inferred from the shape (if they are not set explicitly).
+pub struct FftwArray<T> {
+    dat: FftwVec<T>,
+    shape: Vec<usize>,
+    strides: Vec<isize>,
+}
+
+/// The strides of a contiguous row-major array with the given shape.
+fn row_major(shape: &[usize]) -> Vec<isize> {
+    let mut strides = vec![0; shape.len()];
+    let mut stride = 1;
+    for (s, &n) in strides.iter_mut().zip(shape.iter()).rev() {
+        *s = stride as isize;
+        stride *= n;
+    }
+    strides
+}
+
+/// The number of elements in an array with the given shape, panicking
+/// on overflow.
+fn num_elems(shape: &[usize]) -> usize {
+    shape.iter().fold(1, |n, &m| n.checked_mul(m).expect("FftwArray: size overflow"))
+}
+
+/// The offset of the element at `idx`, if it is in bounds.
+fn offset(shape: &[usize], strides: &[isize], idx: &[usize]) -> Option<isize> {
+    if idx.len() != shape.len() {
+        return None
+    }
+    let mut offset = 0;
+    for ((&i, &n), &s) in idx.iter().zip(shape.iter()).zip(strides.iter()) {
+        if i >= n {
+            return None
+        }
+        offset += i as isize * s;
+    }
+    Some(offset)
+}
+
+/// Types that can be used to index an N-dimensional array.
+pub trait NdIndex {
+    /// The offset of the element in an array with the given shape and
+    /// strides, if it is in bounds.
+    fn offset(&self, shape: &[usize], strides: &[isize]) -> Option<isize>;
+}
+impl NdIndex for usize {
+    fn offset(&self, shape: &[usize], strides: &[isize]) -> Option<isize> {
+        offset(shape, strides, &[*self])
+    }
+}
+impl NdIndex for (usize, usize) {
+    fn offset(&self, shape: &[usize], strides: &[isize]) -> Option<isize> {
+        offset(shape, strides, &[self.0, self.1])
+    }
+}
+impl NdIndex for (usize, usize, usize) {
+    fn offset(&self, shape: &[usize], strides: &[isize]) -> Option<isize> {
+        offset(shape, strides, &[self.0, self.1, self.2])
+    }
+}
+impl<'a> NdIndex for &'a [usize] {
+    fn offset(&self, shape: &[usize], strides: &[isize]) -> Option<isize> {
+        offset(shape, strides, self)
+    }
+}
+
+impl<T> FftwArray<T> {
+    /// Create an array with the given shape, with the element at index
+    /// `idx` being `f(idx)`.
+    pub fn from_fn<F: FnMut(&[usize]) -> T>(shape: &[usize], mut f: F) -> FftwArray<T> {
+        let n = num_elems(shape);
+        let mut idx = vec![0; shape.len()];
+        let dat = FftwVec::from_fn(n, |_| {
+            let x = f(&idx);
+            increment(&mut idx, shape);
+            x
+        });
+        FftwArray::from_vec(shape, dat)
+    }
+
+    /// Interpret `dat` as a row-major array with the given shape.
+    ///
+    /// Panics if the shape does not have exactly `dat.len()` elements.
+    pub fn from_vec(shape: &[usize], dat: FftwVec<T>) -> FftwArray<T> {
+        assert!(!shape.is_empty(), "FftwArray::from_vec: arrays must have at least one dimension");
+        assert!(num_elems(shape) == dat.len(),
+                "FftwArray::from_vec: shape {:?} doesn't match length {}", shape, dat.len());
+        FftwArray { dat: dat, shape: shape.to_vec(), strides: row_major(shape) }
+    }
+
+    /// The length of each axis.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+    /// The distance (in elements) between consecutive elements along
+    /// each axis.
+    pub fn strides(&self) -> &[isize] {
+        &self.strides
+    }
+    /// The number of axes.
+    pub fn ndim(&self) -> usize {
+        self.shape.len()
+    }
+    /// The total number of elements.
+    pub fn len(&self) -> usize {
+        self.dat.len()
+    }
+
+    /// The elements in row-major order.
+    pub fn as_slice(&self) -> &[T] {
+        &self.dat
+    }
+    /// The elements in row-major order.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.dat
+    }
+    /// Discard the shape, returning the elements in row-major order.
+    pub fn into_vec(self) -> FftwVec<T> {
+        self.dat
+    }
+
+    /// A reference to the element at `idx`, if it is in bounds.
+    pub fn get<I: NdIndex>(&self, idx: I) -> Option<&T> {
+        self.view_ref().get_(idx)
+    }
+    /// A mutable reference to the element at `idx`, if it is in bounds.
+    pub fn get_mut<I: NdIndex>(&mut self, idx: I) -> Option<&mut T> {
+        match idx.offset(&self.shape, &self.strides) {
+            Some(o) => Some(&mut self.dat[o as usize]),
+            None => None
+        }
+    }
+
+    /// A view of the whole array.
+    pub fn view(&self) -> ArrayView<T> {
+        self.view_ref()
+    }
+    /// A mutable view of the whole array.
+    pub fn view_mut(&mut self) -> ArrayViewMut<T> {
+        ArrayViewMut {
+            ptr: self.dat.as_mut_ptr(),
+            shape: self.shape.clone(),
+            strides: self.strides.clone(),
+            _marker: PhantomData
+        }
+    }
+    fn view_ref(&self) -> ArrayView<T> {
+        ArrayView {
+            ptr: self.dat.as_ptr(),
+            shape: self.shape.clone(),
+            strides: self.strides.clone(),
+            _marker: PhantomData
+        }
+    }
+
+    /// A view of the elements with indices in `range` along `axis`.
+    pub fn slice_axis(&self, axis: usize, range: Range<usize>) -> ArrayView<T> {
+        self.view().slice_axis(axis, range)
+    }
+    /// A mutable view of the elements with indices in `range` along
+    /// `axis`.
+    pub fn slice_axis_mut(&mut self, axis: usize, range: Range<usize>) -> ArrayViewMut<T> {
+        self.view_mut().slice_axis(axis, range)
+    }
+    /// A view of the elements with index `i` along `axis`, which has
+    /// one fewer axis than this (so the subviews of a one-dimensional
+    /// array are single elements).
+    pub fn subview(&self, axis: usize, i: usize) -> ArrayView<T> {
+        self.view().subview(axis, i)
+    }
+    /// A mutable view of the elements with index `i` along `axis`,
+    /// which has one fewer axis than this.
+    pub fn subview_mut(&mut self, axis: usize, i: usize) -> ArrayViewMut<T> {
+        self.view_mut().subview(axis, i)
+    }
+    /// Iterate over the subviews along `axis`.
+    pub fn axis_iter(&self, axis: usize) -> AxisIter<T> {
+        self.view().axis_iter(axis)
+    }
+    /// Iterate mutably over the subviews along `axis`.
+    pub fn axis_iter_mut(&mut self, axis: usize) -> AxisIterMut<T> {
+        self.view_mut().axis_iter(axis)
+    }
+}
+
+impl<T: Zero> FftwArray<T> {
+    /// Create an array of zeros with the given shape.
+    pub fn zeros(shape: &[usize]) -> FftwArray<T> {
+        FftwArray::from_vec(shape, FftwVec::zeros(num_elems(shape)))
+    }
+}
+
+impl<T, I: NdIndex> Index<I> for FftwArray<T> {
+    type Output = T;
+    fn index(&self, idx: I) -> &T {
+        self.get(idx).expect("FftwArray.index: index out of bounds")
+    }
+}
+impl<T, I: NdIndex> IndexMut<I> for FftwArray<T> {
+    fn index_mut(&mut self, idx: I) -> &mut T {
+        self.get_mut(idx).expect("FftwArray.index_mut: index out of bounds")
+    }
+}
+
+impl<T: Clone> Clone for FftwArray<T> {
+    fn clone(&self) -> FftwArray<T> {
+        FftwArray { dat: self.dat.clone(), shape: self.shape.clone(), strides: self.strides.clone() }
+    }
+}
+impl<T: PartialEq> PartialEq for FftwArray<T> {
+    fn eq(&self, other: &FftwArray<T>) -> bool {
+        self.shape == other.shape && self.dat == other.dat
+    }
+}
+impl<T: Eq> Eq for FftwArray<T> {}
+impl<T: fmt::Debug> fmt::Debug for FftwArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FftwArray {{ shape: {:?}, data: {:?} }}", self.shape, self.dat)
+    }
+}
+
+// Arrays deliberately don't dereference to slices, so that the
+// planner can tell them apart and infer the shape.
+impl<T> Strided for FftwArray<T> {
+    type Elem = T;
+    fn as_stride(&self) -> Stride<T> { Stride::new(self.as_slice()) }
+    fn stride(&self) -> usize { 1 }
+}
+impl<T> MutStrided for FftwArray<T> {
+    fn as_stride_mut(&mut self) -> MutStride<T> { MutStride::new(self.as_mut_slice()) }
+}
+impl<'a, T> Strided for &'a mut FftwArray<T> {
+    type Elem = T;
+    fn as_stride(&self) -> Stride<T> { Stride::new(self.as_slice()) }
+    fn stride(&self) -> usize { 1 }
+}
+impl<'a, T> MutStrided for &'a mut FftwArray<T> {
+    fn as_stride_mut(&mut self) -> MutStride<T> { MutStride::new(self.as_mut_slice()) }
+}
+
+/// Move `idx` to the next index in row-major order, returning `false`
+/// (and resetting it to zero) if it was the last.
+fn increment(idx: &mut [usize], shape: &[usize]) -> bool {
+    for (i, &n) in idx.iter_mut().zip(shape.iter()).rev() {
+        *i += 1;
+        if *i < n {
+            return true
+        }
+        *i = 0;
+    }
+    false
+}
+
+/// Check that `axis` is valid for `shape`, returning its length.
+fn axis_len(shape: &[usize], axis: usize) -> usize {
+    assert!(axis < shape.len(), "FftwArray: axis {} out of bounds for {} dimensions",
+            axis, shape.len());
+    shape[axis]
+}
+
+/// The layout of a view with `axis` restricted to `range`, and the
+/// offset of its first element.
+fn slice_layout(shape: &[usize], strides: &[isize], axis: usize,
+                range: Range<usize>) -> (isize, Vec<usize>) {
+    let n = axis_len(shape, axis);
+    assert!(range.start <= range.end && range.end <= n,
+            "FftwArray: range {:?} out of bounds for axis of length {}", range, n);
+    let mut shape = shape.to_vec();
+    shape[axis] = range.end - range.start;
+    (range.start as isize * strides[axis], shape)
+}
+
+/// The layout of a view with index `i` along `axis`, and the offset
+/// of its first element.
+fn subview_layout(shape: &[usize], strides: &[isize], axis: usize,
+                  i: usize) -> (isize, Vec<usize>, Vec<isize>) {
+    let n = axis_len(shape, axis);
+    assert!(i < n, "FftwArray: index {} out of bounds for axis of length {}", i, n);
+    let (mut shape, mut strides) = (shape.to_vec(), strides.to_vec());
+    shape.remove(axis);
+    let stride = strides.remove(axis);
+    (i as isize * stride, shape, strides)
+}
+
+/// A (possibly non-contiguous) view into an `FftwArray`.
+pub struct ArrayView<'a, T: 'a> {
+    ptr: *const T,
+    shape: Vec<usize>,
+    strides: Vec<isize>,
+    _marker: PhantomData<&'a T>,
+}
+
+/// A mutable (possibly non-contiguous) view into an `FftwArray`.
+pub struct ArrayViewMut<'a, T: 'a> {
+    ptr: *mut T,
+    shape: Vec<usize>,
+    strides: Vec<isize>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+unsafe impl<'a, T: Sync> Send for ArrayView<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for ArrayView<'a, T> {}
+unsafe impl<'a, T: Send> Send for ArrayViewMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for ArrayViewMut<'a, T> {}
+
+impl<'a, T> Clone for ArrayView<'a, T> {
+    fn clone(&self) -> ArrayView<'a, T> {
+        ArrayView {
+            ptr: self.ptr,
+            shape: self.shape.clone(),
+            strides: self.strides.clone(),
+            _marker: PhantomData
+        }
+    }
+}
+
+impl<'a, T> ArrayView<'a, T> {
+    /// The length of each axis.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+    /// The distance (in elements) between consecutive elements along
+    /// each axis.
+    pub fn strides(&self) -> &[isize] {
+        &self.strides
+    }
+
+    /// A reference to the element at `idx`, if it is in bounds.
+    pub fn get<I: NdIndex>(&self, idx: I) -> Option<&T> {
+        self.get_(idx)
+    }
+    fn get_<I: NdIndex>(&self, idx: I) -> Option<&'a T> {
+        match idx.offset(&self.shape, &self.strides) {
+            Some(o) => unsafe {Some(&*self.ptr.offset(o))},
+            None => None
+        }
+    }
+
+    /// A view of the elements with indices in `range` along `axis`.
+    pub fn slice_axis(&self, axis: usize, range: Range<usize>) -> ArrayView<'a, T> {
+        let (offset, shape) = slice_layout(&self.shape, &self.strides, axis, range);
+        ArrayView {
+            ptr: unsafe {self.ptr.offset(offset)},
+            shape: shape,
+            strides: self.strides.clone(),
+            _marker: PhantomData
+        }
+    }
+    /// A view of the elements with index `i` along `axis`, which has
+    /// one fewer axis than this.
+    pub fn subview(&self, axis: usize, i: usize) -> ArrayView<'a, T> {
+        let (offset, shape, strides) = subview_layout(&self.shape, &self.strides, axis, i);
+        ArrayView {
+            ptr: unsafe {self.ptr.offset(offset)},
+            shape: shape,
+            strides: strides,
+            _marker: PhantomData
+        }
+    }
+    /// Iterate over the subviews along `axis`.
+    pub fn axis_iter(&self, axis: usize) -> AxisIter<'a, T> {
+        let n = axis_len(&self.shape, axis);
+        AxisIter { view: self.clone(), axis: axis, idx: 0..n }
+    }
+
+    /// Iterate over the elements in row-major order.
+    pub fn iter(&self) -> Iter<'a, T> {
+        Iter { offsets: Offsets::new(&self.shape, &self.strides), ptr: self.ptr, _marker: PhantomData }
+    }
+
+    /// Copy the elements into a new contiguous array.
+    pub fn to_array(&self) -> FftwArray<T> where T: Clone {
+        let mut it = self.iter();
+        FftwArray::from_vec(&self.shape, FftwVec::from_fn(num_elems(&self.shape), |_| {
+            it.next().unwrap().clone()
+        }))
+    }
+}
+
+impl<'a, T, I: NdIndex> Index<I> for ArrayView<'a, T> {
+    type Output = T;
+    fn index(&self, idx: I) -> &T {
+        self.get(idx).expect("ArrayView.index: index out of bounds")
+    }
+}
+
+impl<'a, T> ArrayViewMut<'a, T> {
+    /// The length of each axis.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+    /// The distance (in elements) between consecutive elements along
+    /// each axis.
+    pub fn strides(&self) -> &[isize] {
+        &self.strides
+    }
+
+    /// An immutable view of the same elements.
+    pub fn view(&self) -> ArrayView<T> {
+        ArrayView {
+            ptr: self.ptr,
+            shape: self.shape.clone(),
+            strides: self.strides.clone(),
+            _marker: PhantomData
+        }
+    }
+
+    /// A reference to the element at `idx`, if it is in bounds.
+    pub fn get<I: NdIndex>(&self, idx: I) -> Option<&T> {
+        match idx.offset(&self.shape, &self.strides) {
+            Some(o) => unsafe {Some(&*self.ptr.offset(o))},
+            None => None
+        }
+    }
+    /// A mutable reference to the element at `idx`, if it is in bounds.
+    pub fn get_mut<I: NdIndex>(&mut self, idx: I) -> Option<&mut T> {
+        match idx.offset(&self.shape, &self.strides) {
+            Some(o) => unsafe {Some(&mut *self.ptr.offset(o))},
+            None => None
+        }
+    }
+
+    /// A view of the elements with indices in `range` along `axis`.
+    pub fn slice_axis(self, axis: usize, range: Range<usize>) -> ArrayViewMut<'a, T> {
+        let (offset, shape) = slice_layout(&self.shape, &self.strides, axis, range);
+        ArrayViewMut {
+            ptr: unsafe {self.ptr.offset(offset)},
+            shape: shape,
+            strides: self.strides,
+            _marker: PhantomData
+        }
+    }
+    /// A view of the elements with index `i` along `axis`, which has
+    /// one fewer axis than this.
+    pub fn subview(self, axis: usize, i: usize) -> ArrayViewMut<'a, T> {
+        let (offset, shape, strides) = subview_layout(&self.shape, &self.strides, axis, i);
+        ArrayViewMut {
+            ptr: unsafe {self.ptr.offset(offset)},
+            shape: shape,
+            strides: strides,
+            _marker: PhantomData
+        }
+    }
+    /// Iterate over the (disjoint) subviews along `axis`.
+    pub fn axis_iter(self, axis: usize) -> AxisIterMut<'a, T> {
+        let n = axis_len(&self.shape, axis);
+        AxisIterMut { view: self, axis: axis, idx: 0..n }
+    }
+
+    /// Iterate over the elements in row-major order.
+    pub fn iter(&self) -> Iter<T> {
+        self.view().iter()
+    }
+    /// Iterate mutably over the elements in row-major order.
+    pub fn iter_mut(&mut self) -> IterMut<T> {
+        IterMut { offsets: Offsets::new(&self.shape, &self.strides), ptr: self.ptr, _marker: PhantomData }
+    }
+}
+
+impl<'a, T, I: NdIndex> Index<I> for ArrayViewMut<'a, T> {
+    type Output = T;
+    fn index(&self, idx: I) -> &T {
+        self.get(idx).expect("ArrayViewMut.index: index out of bounds")
+    }
+}
+impl<'a, T, I: NdIndex> IndexMut<I> for ArrayViewMut<'a, T> {
+    fn index_mut(&mut self, idx: I) -> &mut T {
+        self.get_mut(idx).expect("ArrayViewMut.index_mut: index out of bounds")
+    }
+}
+
+/// An iterator over the subviews along an axis.
+pub struct AxisIter<'a, T: 'a> {
+    view: ArrayView<'a, T>,
+    axis: usize,
+    idx: Range<usize>,
+}
+impl<'a, T> Iterator for AxisIter<'a, T> {
+    type Item = ArrayView<'a, T>;
+    fn next(&mut self) -> Option<ArrayView<'a, T>> {
+        match self.idx.next() {
+            Some(i) => Some(self.view.subview(self.axis, i)),
+            None => None
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.idx.size_hint()
+    }
+}
+
+/// An iterator over the mutable subviews along an axis.
+pub struct AxisIterMut<'a, T: 'a> {
+    view: ArrayViewMut<'a, T>,
+    axis: usize,
+    idx: Range<usize>,
+}
+impl<'a, T> Iterator for AxisIterMut<'a, T> {
+    type Item = ArrayViewMut<'a, T>;
+    fn next(&mut self) -> Option<ArrayViewMut<'a, T>> {
+        match self.idx.next() {
+            Some(i) => {
+                // each index is only visited once, so the subviews are
+                // disjoint.
+                let view = ArrayViewMut {
+                    ptr: self.view.ptr,
+                    shape: self.view.shape.clone(),
+                    strides: self.view.strides.clone(),
+                    _marker: PhantomData
+                };
+                Some(view.subview(self.axis, i))
+            }
+            None => None
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.idx.size_hint()
+    }
+}
+
+/// The offsets of every element of a strided array, in row-major
+/// order.
+struct Offsets {
+    shape: Vec<usize>,
+    strides: Vec<isize>,
+    idx: Vec<usize>,
+    done: bool,
+}
+impl Offsets {
+    fn new(shape: &[usize], strides: &[isize]) -> Offsets {
+        Offsets {
+            shape: shape.to_vec(),
+            strides: strides.to_vec(),
+            idx: vec![0; shape.len()],
+            done: shape.iter().any(|&n| n == 0),
+        }
+    }
+}
+impl Iterator for Offsets {
+    type Item = isize;
+    fn next(&mut self) -> Option<isize> {
+        if self.done {
+            return None
+        }
+        let o = self.idx.iter().zip(self.strides.iter()).fold(0, |o, (&i, &s)| o + i as isize * s);
+        self.done = !increment(&mut self.idx, &self.shape);
+        Some(o)
+    }
+}
+
+/// An iterator over the elements of a view.
+pub struct Iter<'a, T: 'a> {
+    offsets: Offsets,
+    ptr: *const T,
+    _marker: PhantomData<&'a T>,
+}
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        match self.offsets.next() {
+            Some(o) => unsafe {Some(&*self.ptr.offset(o))},
+            None => None
+        }
+    }
+}
+
+/// A mutable iterator over the elements of a view.
+pub struct IterMut<'a, T: 'a> {
+    offsets: Offsets,
+    ptr: *mut T,
+    _marker: PhantomData<&'a mut T>,
+}
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> {
+        match self.offsets.next() {
+            Some(o) => unsafe {Some(&mut *self.ptr.offset(o))},
+            None => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FftwArray;
+
+    #[test]
+    fn indexing() {
+        let mut a = FftwArray::from_fn(&[2, 3, 4], |i| i[0] * 100 + i[1] * 10 + i[2]);
+        assert_eq!(a.shape(), &[2, 3, 4]);
+        assert_eq!(a.strides(), &[12, 4, 1]);
+        assert_eq!(a[(1, 2, 3)], 123);
+        assert_eq!(a[&[0, 1, 2][..]], 12);
+        assert_eq!(a.get((2, 0, 0)), None);
+        assert_eq!(a.get((0, 0)), None);
+        a[(0, 0, 1)] = 7;
+        assert_eq!(a.as_slice()[1], 7);
+    }
+
+    #[test]
+    fn views() {
+        let mut a = FftwArray::from_fn(&[3, 4], |i| i[0] * 10 + i[1]);
+        {
+            let s = a.slice_axis(1, 1..3);
+            assert_eq!(s.shape(), &[3, 2]);
+            assert_eq!(s.iter().cloned().collect::<Vec<_>>(), [1, 2, 11, 12, 21, 22]);
+            assert_eq!(s.to_array(), FftwArray::from_fn(&[3, 2], |i| i[0] * 10 + i[1] + 1));
+
+            let col = a.subview(1, 3);
+            assert_eq!(col.shape(), &[3]);
+            assert_eq!(col.iter().cloned().collect::<Vec<_>>(), [3, 13, 23]);
+            assert_eq!(col.axis_iter(0).map(|x| x[&[][..]]).collect::<Vec<_>>(), [3, 13, 23]);
+
+            let rows = a.axis_iter(0).map(|r| r[3]).collect::<Vec<_>>();
+            assert_eq!(rows, [3, 13, 23]);
+        }
+
+        for (j, mut col) in a.axis_iter_mut(1).enumerate() {
+            for x in col.iter_mut() {
+                *x = j;
+            }
+        }
+        assert_eq!(a.as_slice(), &[0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3]);
+
+        let empty = a.slice_axis(0, 1..1);
+        assert_eq!(empty.iter().count(), 0);
+    }
+}
@@ -0,0 +1,653 @@
+//! Memory backing.
+
+use ffi;
+use traits::Zero;
+use libc;
+use std::{fmt, iter, mem, ptr, raw, slice};
+use std::ops::{Deref, DerefMut};
+
+pub use self::array::{FftwArray, ArrayView, ArrayViewMut, NdIndex,
+                      AxisIter, AxisIterMut, Iter, IterMut};
+pub use self::pool::{Pool, Pooled};
+
+mod array;
+mod pool;
+
+/// The alignment in bytes that FFTW's SIMD code requires of arrays,
+/// which memory from FFTW's allocator always has.
+pub fn simd_alignment() -> usize {
+    // `fftw_alignment_of` is the offset from the nearest multiple of
+    // the alignment below, so find two consecutive aligned elements
+    // of an array.
+    let probe = [0f64; 65];
+    let aligned = (0..probe.len()).filter(|&i| unsafe {
+        ffi::fftw_alignment_of(probe.as_ptr().offset(i as isize) as *mut f64) == 0
+    }).take(2).collect::<Vec<_>>();
+
+    let stride = if aligned.len() == 2 { aligned[1] - aligned[0] } else { probe.len() };
+    stride * mem::size_of::<f64>()
+}
+
+struct RawVec<T> {
+    dat: *mut T,
+    len: usize
+}
+impl<T> RawVec<T> {
+    unsafe fn uninit(n: usize) -> RawVec<T> {
+        let size = n.checked_mul(mem::size_of::<T>()).expect("FftwVec::uninit: size overflow");
+        if size == 0 {
+            // `fftw_malloc(0)` may return NULL, so don't allocate at
+            // all, just use a non-null, aligned, dangling pointer.
+            return RawVec { dat: mem::align_of::<T>() as *mut T, len: n }
+        }
+
+        let dat = ffi::fftw_malloc(size as libc::size_t);
+
+        if dat.is_null() {
+            panic!("FftwVec::uninit: fftw_malloc failed");
+        }
+
+        RawVec { dat: dat as *mut T, len: n }
+    }
+}
+
+impl<T> Deref for RawVec<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        unsafe {mem::transmute(raw::Slice { data: self.dat as *const T, len: self.len })}
+    }
+}
+impl<T> DerefMut for RawVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe {mem::transmute(raw::Slice { data: self.dat as *const T, len: self.len })}
+    }
+}
+
+impl<T> Drop for RawVec<T> {
+    fn drop(&mut self) {
+        if self.len * mem::size_of::<T>() != 0 {
+            unsafe {ffi::fftw_free(self.dat as *mut libc::c_void)}
+        }
+    }
+}
+
+/// A non-resizable vector allocated using FFTWs allocator.
+///
+/// This implements `Deref<[T]>` and `DerefMut<[T]>` and so can be
+/// used nearly-interchangeably with slices.
+pub struct FftwVec<T> {
+    dat: RawVec<T>
+}
+
+impl<T> FftwVec<T> {
+    /// Allocate space for `n` elements without initialising them.
+    ///
+    /// The elements can only be accessed through raw pointers until
+    /// the vector is initialised (e.g. with `UninitFftwVec::zeroed`).
+    pub fn uninit(n: usize) -> UninitFftwVec<T> {
+        UninitFftwVec { dat: unsafe {RawVec::uninit(n)} }
+    }
+}
+impl<T> Deref for FftwVec<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &*self.dat
+    }
+}
+impl<T> DerefMut for FftwVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut *self.dat
+    }
+}
+
+impl<T> Drop for FftwVec<T> {
+    fn drop(&mut self) {
+        // free everything
+        for p in self.iter() {
+            unsafe {ptr::read(p);}
+        }
+    }
+}
+
+// `FftwVec` owns its elements, just like `Vec`.
+unsafe impl<T: Send> Send for FftwVec<T> {}
+unsafe impl<T: Sync> Sync for FftwVec<T> {}
+unsafe impl<T: Send> Send for UninitFftwVec<T> {}
+unsafe impl<T: Sync> Sync for UninitFftwVec<T> {}
+
+impl<T> FftwVec<T> {
+    /// Create a `FftwVec` of length `n`, with the `i`th element `f(i)`.
+    pub fn from_fn<F: FnMut(usize) -> T>(n: usize, f: F) -> FftwVec<T> {
+        FftwVec::uninit(n).init_with(f)
+    }
+
+    /// Move the elements into a `Vec`.
+    pub fn into_vec(self) -> Vec<T> {
+        self.into_iter().collect()
+    }
+}
+
+impl<T: Clone> FftwVec<T> {
+    /// Create a `FftwVec` containing a copy of `elems`.
+    pub fn from_slice(elems: &[T]) -> FftwVec<T> {
+        FftwVec::from_fn(elems.len(), |i| elems[i].clone())
+    }
+
+    /// Create a `FftwVec` containing `n` copies of `elem`.
+    pub fn from_elem(n: usize, elem: T) -> FftwVec<T> {
+        FftwVec::from_fn(n, |_| elem.clone())
+    }
+}
+
+impl<T: Clone> Clone for FftwVec<T> {
+    fn clone(&self) -> FftwVec<T> {
+        FftwVec::from_slice(self)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for FftwVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for FftwVec<T> {
+    fn eq(&self, other: &FftwVec<T>) -> bool {
+        **self == **other
+    }
+}
+impl<T: Eq> Eq for FftwVec<T> {}
+
+impl<T> From<Vec<T>> for FftwVec<T> {
+    /// Move the elements of `v` into FFTW-aligned storage.
+    fn from(mut v: Vec<T>) -> FftwVec<T> {
+        let mut ret = FftwVec::uninit(v.len());
+        unsafe {
+            ptr::copy_nonoverlapping(v.as_ptr(), ret.as_mut_ptr(), v.len());
+            // the elements have been moved out.
+            v.set_len(0);
+            ret.assume_init()
+        }
+    }
+}
+
+impl<T> From<FftwVec<T>> for Vec<T> {
+    fn from(v: FftwVec<T>) -> Vec<T> {
+        v.into_vec()
+    }
+}
+
+impl<T> iter::FromIterator<T> for FftwVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> FftwVec<T> {
+        // the length isn't known in advance, so collect into a `Vec`
+        // first.
+        FftwVec::from(it.into_iter().collect::<Vec<T>>())
+    }
+}
+
+/// An iterator that moves out of a `FftwVec`.
+pub struct IntoIter<T> {
+    dat: RawVec<T>,
+    idx: usize,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.idx < self.dat.len {
+            let x = unsafe {ptr::read(self.dat.dat.offset(self.idx as isize))};
+            self.idx += 1;
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.dat.len - self.idx;
+        (n, Some(n))
+    }
+}
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        // drop the elements that weren't yielded
+        for _ in self.by_ref() {}
+    }
+}
+
+impl<T> IntoIterator for FftwVec<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        unsafe {
+            let dat = ptr::read(&self.dat);
+            mem::forget(self);
+            IntoIter { dat: dat, idx: 0 }
+        }
+    }
+}
+impl<'a, T> IntoIterator for &'a FftwVec<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+impl<'a, T> IntoIterator for &'a mut FftwVec<T> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+/// Memory allocated by FFTW's allocator for a `FftwVec`, whose
+/// elements have not been initialised.
+///
+/// This is useful for planning, which (for rigor levels above
+/// `Estimate`) overwrites the arrays anyway, so there is no point
+/// initialising them until afterwards.
+pub struct UninitFftwVec<T> {
+    dat: RawVec<T>
+}
+
+impl<T> UninitFftwVec<T> {
+    /// The number of elements there is space for.
+    pub fn len(&self) -> usize {
+        self.dat.len
+    }
+
+    /// A pointer to the start of the memory, which can be written to
+    /// but must not be read until it has been initialised.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.dat.dat
+    }
+
+    /// Initialise the `i`th element to `f(i)`.
+    pub fn init_with<F: FnMut(usize) -> T>(self, mut f: F) -> FftwVec<T> {
+        let mut v = PartialVec {
+            dat: self.dat,
+            idx: 0,
+        };
+
+        while v.idx < v.dat.len {
+            unsafe {
+                ptr::write(v.dat.dat.offset(v.idx as isize), f(v.idx));
+            }
+            v.idx += 1
+        }
+
+        unsafe {
+            let ret = FftwVec {
+                dat: ptr::read(&v.dat)
+            };
+            mem::forget(v);
+            ret
+        }
+    }
+
+    /// Assert that every element has been initialised, e.g. by
+    /// writing through `as_mut_ptr`.
+    pub unsafe fn assume_init(self) -> FftwVec<T> {
+        FftwVec { dat: self.dat }
+    }
+}
+
+impl<T: Zero> UninitFftwVec<T> {
+    /// Initialise every element to zero.
+    pub fn zeroed(self) -> FftwVec<T> {
+        self.init_with(|_| Zero::zero())
+    }
+}
+
+struct PartialVec<T> {
+    dat: RawVec<T>,
+    idx: usize
+}
+
+impl<T: Zero> FftwVec<T> {
+    /// Allocate a `FftwVec` of length `n` containing zeros.
+    pub fn zeros(n: usize) -> FftwVec<T> {
+        FftwVec::uninit(n).zeroed()
+    }
+}
+
+impl<T> Drop for PartialVec<T> {
+    fn drop(&mut self) {
+        for p in self.dat[..self.idx].iter() {
+            unsafe {ptr::read(p);}
+        }
+    }
+}
+
+/// A growable vector allocated using FFTW's allocator.
+///
+/// Like `FftwVec`, the elements are always suitably aligned for FFTW's
+/// SIMD code, including after the buffer has grown. It dereferences
+/// to a slice, so can be given to either planner; note that growing
+/// beyond the capacity moves the elements, and any plans created with
+/// the old memory must not be executed with the new.
+pub struct FftwBuffer<T> {
+    dat: RawVec<T>,
+    len: usize,
+}
+
+impl<T> FftwBuffer<T> {
+    /// Create an empty buffer, without allocating.
+    pub fn new() -> FftwBuffer<T> {
+        FftwBuffer::with_capacity(0)
+    }
+
+    /// Create an empty buffer with space for `n` elements.
+    pub fn with_capacity(n: usize) -> FftwBuffer<T> {
+        FftwBuffer { dat: unsafe {RawVec::uninit(n)}, len: 0 }
+    }
+
+    /// The number of elements that can be stored without
+    /// reallocating.
+    pub fn capacity(&self) -> usize {
+        if mem::size_of::<T>() == 0 { !0 } else { self.dat.len }
+    }
+
+    /// Ensure there is space for at least `additional` more elements.
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len.checked_add(additional).expect("FftwBuffer::reserve: size overflow");
+        let cap = self.capacity();
+        if required <= cap {
+            return
+        }
+        // grow geometrically, so that pushing is amortised O(1)
+        let new_cap = ::std::cmp::max(required, cap.saturating_mul(2));
+        unsafe {
+            let new = RawVec::uninit(new_cap);
+            ptr::copy_nonoverlapping(self.dat.dat, new.dat, self.len);
+            // the old memory is freed without dropping anything.
+            self.dat = new;
+        }
+    }
+
+    /// Append `x` to the end of the buffer.
+    pub fn push(&mut self, x: T) {
+        if self.len == self.capacity() {
+            self.reserve(1)
+        }
+        unsafe {
+            ptr::write(self.dat.dat.offset(self.len as isize), x);
+        }
+        self.len += 1;
+    }
+
+    /// Remove and return the last element, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            None
+        } else {
+            self.len -= 1;
+            unsafe {Some(ptr::read(self.dat.dat.offset(self.len as isize)))}
+        }
+    }
+
+    /// Shorten the buffer to `len` elements, dropping the rest. This
+    /// does nothing if the buffer is already shorter.
+    ///
+    /// The capacity is unchanged.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            self.pop();
+        }
+    }
+
+    /// Remove every element.
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+
+    /// Move the elements into a `FftwVec`, reusing the memory if the
+    /// buffer is full, and otherwise reallocating without the spare
+    /// capacity.
+    pub fn into_fftw_vec(self) -> FftwVec<T> {
+        if self.len == self.dat.len {
+            unsafe {
+                let dat = ptr::read(&self.dat);
+                mem::forget(self);
+                return FftwVec { dat: dat }
+            }
+        }
+        let mut v = FftwVec::uninit(self.len);
+        unsafe {
+            ptr::copy_nonoverlapping(self.dat.dat, v.as_mut_ptr(), self.len);
+            let dat = ptr::read(&self.dat);
+            mem::forget(self);
+            // just free the old memory.
+            drop(dat);
+            v.assume_init()
+        }
+    }
+}
+
+impl<T: Clone> FftwBuffer<T> {
+    /// Resize the buffer to `len` elements, truncating or filling
+    /// the new space with copies of `value` as appropriate.
+    pub fn resize(&mut self, len: usize, value: T) {
+        if len <= self.len {
+            self.truncate(len)
+        } else {
+            self.reserve(len - self.len);
+            while self.len < len {
+                self.push(value.clone())
+            }
+        }
+    }
+
+    /// Append a copy of every element of `elems`.
+    pub fn extend_from_slice(&mut self, elems: &[T]) {
+        self.reserve(elems.len());
+        for x in elems.iter() {
+            self.push(x.clone())
+        }
+    }
+}
+
+impl<T> Deref for FftwBuffer<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        // `dat` may be shorter than `len` for zero-sized types.
+        unsafe {mem::transmute(raw::Slice { data: self.dat.dat as *const T, len: self.len })}
+    }
+}
+impl<T> DerefMut for FftwBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe {mem::transmute(raw::Slice { data: self.dat.dat as *const T, len: self.len })}
+    }
+}
+
+impl<T> Drop for FftwBuffer<T> {
+    fn drop(&mut self) {
+        self.clear()
+    }
+}
+
+unsafe impl<T: Send> Send for FftwBuffer<T> {}
+unsafe impl<T: Sync> Sync for FftwBuffer<T> {}
+
+impl<T> Extend<T> for FftwBuffer<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        let it = it.into_iter();
+        self.reserve(it.size_hint().0);
+        for x in it {
+            self.push(x)
+        }
+    }
+}
+
+impl<T> iter::FromIterator<T> for FftwBuffer<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> FftwBuffer<T> {
+        let mut b = FftwBuffer::new();
+        b.extend(it);
+        b
+    }
+}
+
+impl<T> From<FftwVec<T>> for FftwBuffer<T> {
+    fn from(v: FftwVec<T>) -> FftwBuffer<T> {
+        unsafe {
+            let dat = ptr::read(&v.dat);
+            mem::forget(v);
+            FftwBuffer { len: dat.len, dat: dat }
+        }
+    }
+}
+
+impl<T: Clone> Clone for FftwBuffer<T> {
+    fn clone(&self) -> FftwBuffer<T> {
+        let mut b = FftwBuffer::with_capacity(self.len);
+        b.extend_from_slice(self);
+        b
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for FftwBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for FftwBuffer<T> {
+    fn eq(&self, other: &FftwBuffer<T>) -> bool {
+        **self == **other
+    }
+}
+impl<T: Eq> Eq for FftwBuffer<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use mem::{FftwVec, FftwBuffer};
+
+    struct Counted<'a>(&'a Cell<usize>);
+    impl<'a> Drop for Counted<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1)
+        }
+    }
+
+    #[test]
+    fn uninit() {
+        let drops = Cell::new(0);
+        drop(FftwVec::<Counted>::uninit(10));
+        assert_eq!(drops.get(), 0);
+
+        let v = FftwVec::uninit(10).init_with(|_| Counted(&drops));
+        assert_eq!(v.len(), 10);
+        drop(v);
+        assert_eq!(drops.get(), 10);
+
+        let v = FftwVec::<f64>::uninit(10).zeroed();
+        assert!(v.iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn empty() {
+        let v = FftwVec::<f64>::zeros(0);
+        assert_eq!(v.len(), 0);
+        assert_eq!(v.into_vec(), vec![]);
+
+        let v = FftwVec::from_elem(5, ());
+        assert_eq!(v.len(), 5);
+    }
+
+    #[test]
+    fn conversions() {
+        let v = FftwVec::from_fn(10, |i| i * 2);
+        let w = v.clone();
+        assert_eq!(v, w);
+        assert_eq!(w, FftwVec::from_slice(&[0, 2, 4, 6, 8, 10, 12, 14, 16, 18]));
+        assert_eq!(format!("{:?}", FftwVec::from_elem(2, 1)), "[1, 1]");
+
+        let vec: Vec<_> = v.into();
+        assert_eq!(vec, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(FftwVec::from(vec.clone()), w);
+        assert_eq!((0..10).map(|i| i * 2).collect::<FftwVec<_>>(), w);
+
+        let strings = FftwVec::from_elem(3, "a".to_string());
+        let mut it = strings.into_iter();
+        assert_eq!(it.next(), Some("a".to_string()));
+        // dropping the rest mustn't leak or double-free
+        drop(it);
+    }
+
+    #[test]
+    fn fftw_vec() {
+        let mut v = FftwVec::<usize>::zeros(100);
+        for (i, x) in v.iter_mut().enumerate() {
+            *x = i;
+        }
+        let mut i = 0;
+        for x in v.iter() {
+            assert_eq!(*x, i);
+            i += 1;
+        }
+        assert_eq!(i, 100);
+    }
+
+    #[test]
+    fn buffer() {
+        let drops = Cell::new(0);
+        let mut b = FftwBuffer::new();
+        for _ in 0..100 {
+            b.push(Counted(&drops));
+        }
+        assert_eq!(b.len(), 100);
+        assert!(b.capacity() >= 100);
+        b.truncate(10);
+        assert_eq!(drops.get(), 90);
+        drop(b);
+        assert_eq!(drops.get(), 100);
+
+        let mut b = FftwBuffer::with_capacity(1);
+        b.extend(0..5);
+        b.extend_from_slice(&[5, 6]);
+        let cap = b.capacity();
+        b.resize(3, 0);
+        assert_eq!(&*b, &[0, 1, 2]);
+        assert_eq!(b.capacity(), cap);
+        b.resize(5, 9);
+        assert_eq!(&*b, &[0, 1, 2, 9, 9]);
+        assert_eq!(b.pop(), Some(9));
+        // a clone has no spare capacity, so its memory is reused.
+        let full = b.clone();
+        let ptr = full.as_ptr();
+        let v = full.into_fftw_vec();
+        assert_eq!(v.as_ptr(), ptr);
+        assert_eq!(v, FftwVec::from_slice(&[0, 1, 2, 9]));
+        assert_eq!(b.into_fftw_vec(), FftwVec::from_slice(&[0, 1, 2, 9]));
+    }
+
+    #[test]
+    fn simd_alignment() {
+        let align = super::simd_alignment();
+        assert!(align.is_power_of_two() && align >= 8, "{}", align);
+        let v = FftwVec::<f64>::zeros(3);
+        assert_eq!(v.as_ptr() as usize % align, 0);
+    }
+
+    #[test]
+    fn buffer_alignment() {
+        use ffi;
+        use num::complex::Complex64;
+
+        let mut b = FftwBuffer::<Complex64>::new();
+        let mut in_ = FftwVec::<Complex64>::zeros(1);
+        for n in 1..100 {
+            b.push(Complex64::new(n as f64, 0.0));
+            // FFTW's alignment check compares against an allocation of
+            // its own.
+            unsafe {
+                assert_eq!(ffi::fftw_alignment_of(b.as_mut_ptr() as *mut f64),
+                           ffi::fftw_alignment_of(in_.as_mut_ptr() as *mut f64));
+            }
+        }
+    }
+}
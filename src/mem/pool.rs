@@ -0,0 +1,196 @@
+//! Recycling FFTW-aligned buffers.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use traits::Zero;
+
+use super::FftwVec;
+
+/// The number of free buffers kept for each size class by default.
+const DEFAULT_LIMIT: usize = 16;
+
+struct Inner<T> {
+    free: HashMap<usize, Vec<FftwVec<T>>>,
+    limit: usize,
+}
+
+/// A thread-safe pool of FFTW-aligned buffers.
+///
+/// Buffers are grouped into size classes (powers of two) so that
+/// requests of similar sizes can share memory, and are returned to
+/// the pool when the `Pooled` handle is dropped. Cloning a `Pool`
+/// gives another handle to the same buffers.
+///
+/// The elements of a recycled buffer are whatever the previous user
+/// left there; use `get_zeroed` if that matters.
+pub struct Pool<T> {
+    inner: Arc<Mutex<Inner<T>>>
+}
+
+/// The size class that a buffer of `n` elements is allocated from.
+fn size_class(n: usize) -> usize {
+    n.checked_next_power_of_two().unwrap_or(n)
+}
+
+impl<T: Copy + Zero> Pool<T> {
+    /// Create an empty pool.
+    pub fn new() -> Pool<T> {
+        Pool::with_limit(DEFAULT_LIMIT)
+    }
+
+    /// Create an empty pool that keeps at most `limit` free buffers
+    /// of each size class, freeing any others.
+    pub fn with_limit(limit: usize) -> Pool<T> {
+        Pool { inner: Arc::new(Mutex::new(Inner { free: HashMap::new(), limit: limit })) }
+    }
+
+    /// Take a buffer of `n` elements from the pool, allocating a new
+    /// one if there is none free.
+    pub fn get(&self, n: usize) -> Pooled<T> {
+        let class = size_class(n);
+        let recycled = self.inner.lock().unwrap().free.get_mut(&class).and_then(|v| v.pop());
+        let dat = match recycled {
+            Some(v) => v,
+            // allocating doesn't need the lock.
+            None => FftwVec::zeros(class),
+        };
+        Pooled { dat: Some(dat), len: n, pool: self.inner.clone() }
+    }
+
+    /// Take a buffer of `n` zeros from the pool.
+    pub fn get_zeroed(&self, n: usize) -> Pooled<T> {
+        let mut b = self.get(n);
+        for x in b.iter_mut() {
+            *x = Zero::zero()
+        }
+        b
+    }
+
+    /// The number of free buffers held by the pool.
+    pub fn free_buffers(&self) -> usize {
+        self.inner.lock().unwrap().free.values().fold(0, |n, v| n + v.len())
+    }
+
+    /// Free every buffer that isn't in use.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().free.clear()
+    }
+}
+
+impl<T> Clone for Pool<T> {
+    fn clone(&self) -> Pool<T> {
+        Pool { inner: self.inner.clone() }
+    }
+}
+
+/// A buffer borrowed from a `Pool`, which is returned when this is
+/// dropped.
+///
+/// This dereferences to a slice of the requested length, so can be
+/// used as the input or output of either planner.
+pub struct Pooled<T> {
+    // always `Some` until dropped.
+    dat: Option<FftwVec<T>>,
+    len: usize,
+    pool: Arc<Mutex<Inner<T>>>,
+}
+
+impl<T> Deref for Pooled<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.dat.as_ref().unwrap()[..self.len]
+    }
+}
+impl<T> DerefMut for Pooled<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        let len = self.len;
+        &mut self.dat.as_mut().unwrap()[..len]
+    }
+}
+
+impl<T> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        let dat = self.dat.take().unwrap();
+        // if another user panicked while holding the lock, just free
+        // the buffer.
+        if let Ok(mut inner) = self.pool.lock() {
+            let limit = inner.limit;
+            let free = inner.free.entry(dat.len()).or_insert(vec![]);
+            if free.len() < limit {
+                free.push(dat)
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Pooled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use num::complex::Complex64;
+    use plan::Plan;
+    use super::Pool;
+
+    #[test]
+    fn recycles() {
+        let pool = Pool::<f64>::new();
+        let ptr = {
+            let mut b = pool.get(100);
+            assert_eq!(b.len(), 100);
+            b[99] = 1.0;
+            b.as_ptr()
+        };
+        assert_eq!(pool.free_buffers(), 1);
+
+        // the same size class reuses the buffer, another doesn't.
+        let b = pool.get(120);
+        assert_eq!(b.as_ptr(), ptr);
+        assert_eq!(pool.get_zeroed(120).iter().filter(|x| **x != 0.0).count(), 0);
+        drop(b);
+        assert_eq!(pool.free_buffers(), 2);
+        assert!(pool.get(10).as_ptr() != ptr);
+        assert_eq!(pool.free_buffers(), 3);
+
+        pool.clear();
+        assert_eq!(pool.free_buffers(), 0);
+
+        let limited = Pool::<f64>::with_limit(1);
+        drop((limited.get(4), limited.get(4)));
+        assert_eq!(limited.free_buffers(), 1);
+    }
+
+    #[test]
+    fn threads() {
+        let pool = Pool::<Complex64>::new();
+        let threads = (0..4).map(|_| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for n in 1..50 {
+                    let in_ = pool.get_zeroed(n);
+                    let out = pool.get(n / 2 + 1);
+                    drop((in_, out));
+                }
+            })
+        }).collect::<Vec<_>>();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert!(pool.free_buffers() > 0);
+    }
+
+    #[test]
+    fn plan_with_pooled() {
+        let reals = Pool::<f64>::new();
+        let complexes = Pool::<Complex64>::new();
+        let mut plan = Plan::r2c_1d_prealloc(reals.get_zeroed(8), complexes.get(5)).unwrap();
+        plan.input()[0] = 1.0;
+        assert!(plan.execute().iter().all(|x| (x.re - 1.0).abs() < 1e-10 && x.im.abs() < 1e-10));
+    }
+}
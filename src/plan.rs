@@ -1,6 +1,9 @@
-use {ffi, lock};
+use {ffi, lock, context, error};
+use libc::{c_int, c_uint};
 use std::ops::DerefMut;
 use mem::FftwVec;
+use builder2::{Direction, Rigor};
+use error::{Buffer, Error, PlanResult};
 
 use num::complex::Complex64;
 
@@ -15,18 +18,27 @@ impl RawPlan {
     /// This executes `f` inside a lock since FFTW plan creation is
     /// not threadsafe.
     pub fn new<F: FnOnce() -> ffi::fftw_plan>(f: F) -> Option<RawPlan> {
-        let plan = lock::run(f);
+        let plan = lock::run(|| {
+            let plan = f();
 
-        if plan.is_null() {
-            None
-        } else {
-            Some(RawPlan { plan: plan })
-        }
+            if plan.is_null() {
+                None
+            } else {
+                context::plan_created();
+                Some(RawPlan { plan: plan })
+            }
+        });
+        lock::check_hook_panic();
+        plan
     }
 
     /// Create a `RawPlan` directly from an `fftw_plan`, with no
-    /// synchronisation or checks. Prefer `RawPlan::new` where possible.
+    /// synchronisation. Prefer `RawPlan::new` where possible.
+    ///
+    /// Panics if `plan` is null.
     pub unsafe fn new_unchecked(plan: ffi::fftw_plan) -> RawPlan {
+        assert!(!plan.is_null(), "RawPlan::new_unchecked: null plan");
+        context::plan_created();
         RawPlan { plan: plan }
     }
 
@@ -38,11 +50,66 @@ impl RawPlan {
     pub unsafe fn execute(&mut self) {
         ffi::fftw_execute(self.plan)
     }
+
+    /// The cost of executing the plan as measured by the planner, in
+    /// arbitrary units, or 0 if it wasn't measured (e.g. with
+    /// `FFTW_ESTIMATE`).
+    pub fn cost(&self) -> f64 {
+        unsafe {ffi::fftw_cost(self.plan)}
+    }
+
+    /// FFTW's heuristic estimate of the cost of executing the plan.
+    pub fn estimate_cost(&self) -> f64 {
+        unsafe {ffi::fftw_estimate_cost(self.plan)}
+    }
+
+    /// Execute a complex-to-complex plan on new arrays, which must
+    /// have the same layout and alignment as those it was planned
+    /// with.
+    ///
+    /// Unlike `execute`, this can be called from several threads at
+    /// once, given distinct arrays.
+    pub unsafe fn execute_dft(&self, in_: *mut Complex64, out: *mut Complex64) {
+        ffi::fftw_execute_dft(self.plan, in_ as *mut _, out as *mut _)
+    }
+    /// Execute a real-to-complex plan on new arrays, as for
+    /// `execute_dft`.
+    pub unsafe fn execute_dft_r2c(&self, in_: *mut f64, out: *mut Complex64) {
+        ffi::fftw_execute_dft_r2c(self.plan, in_, out as *mut _)
+    }
+    /// Execute a complex-to-real plan on new arrays, as for
+    /// `execute_dft`.
+    pub unsafe fn execute_dft_c2r(&self, in_: *mut Complex64, out: *mut f64) {
+        ffi::fftw_execute_dft_c2r(self.plan, in_ as *mut _, out)
+    }
+    /// Execute a real-to-real plan on new arrays, as for
+    /// `execute_dft`.
+    pub unsafe fn execute_r2r(&self, in_: *mut f64, out: *mut f64) {
+        ffi::fftw_execute_r2r(self.plan, in_, out)
+    }
+}
+
+/// Create a `RawPlan` from `f`, which plans using `flags`, explaining
+/// why FFTW failed if it does.
+pub fn plan_with_flags<F: FnOnce() -> ffi::fftw_plan>(flags: c_uint, f: F) -> PlanResult<RawPlan> {
+    match RawPlan::new(f) {
+        Some(p) => Ok(p),
+        None if flags & ffi::FFTW_WISDOM_ONLY != 0 => Err(Error::WisdomMissing),
+        None => Err(Error::FftwError),
+    }
 }
 
+// a plan is only ever executed through a `&mut`, or with FFTW's
+// thread-safe new-array functions, and is destroyed inside the lock,
+// so it can move between threads.
+unsafe impl Send for RawPlan {}
+
 impl Drop for RawPlan {
     fn drop(&mut self) {
-        unsafe {ffi::fftw_destroy_plan(self.plan)}
+        lock::run(|| {
+            unsafe {ffi::fftw_destroy_plan(self.plan)}
+            context::plan_destroyed();
+        })
     }
 }
 
@@ -79,90 +146,349 @@ impl<In, Out: DerefMut> Plan<In, Out> {
     }
 }
 
+/// Options for the `Plan` constructors.
+///
+/// The defaults are `Estimate` rigor, the natural direction for the
+/// transform and no extra flags.
+#[derive(Clone, Copy)]
+pub struct PlanOptions {
+    /// How much effort FFTW should put into planning. Anything more
+    /// than `Estimate` overwrites the buffers while planning.
+    pub rigor: Rigor,
+    /// The direction of a complex-to-complex transform (forward if
+    /// `None`). Real-to-complex transforms are always forward and
+    /// complex-to-real ones always backward, so setting the other
+    /// direction for these is an error.
+    pub direction: Option<Direction>,
+    /// Extra FFTW flags, e.g. `FFTW_DESTROY_INPUT` or
+    /// `FFTW_WISDOM_ONLY`.
+    pub flags: c_uint,
+}
+
+impl Default for PlanOptions {
+    fn default() -> PlanOptions {
+        PlanOptions { rigor: Rigor::Estimate, direction: None, flags: 0 }
+    }
+}
+
+impl PlanOptions {
+    fn flags(&self) -> c_uint {
+        self.flags | match self.rigor {
+            Rigor::Estimate => ffi::FFTW_ESTIMATE,
+            Rigor::Measure => ffi::FFTW_MEASURE,
+            Rigor::Patient => ffi::FFTW_PATIENT,
+            Rigor::Exhaustive => ffi::FFTW_EXHAUSTIVE,
+        }
+    }
+
+    fn sign(&self) -> c_int {
+        match self.direction {
+            None | Some(Direction::Forward) => ffi::FFTW_FORWARD,
+            Some(Direction::Backward) => ffi::FFTW_BACKWARD,
+        }
+    }
+}
+
+/// The dimensions of a transform, checked to be suitable for FFTW's
+/// simple interface.
+struct Dims {
+    n: Vec<c_int>,
+    /// The number of elements of the full array.
+    total: usize,
+    /// The number of elements of the complex side of a real transform,
+    /// which only has `n / 2 + 1` along the last axis.
+    half: usize,
+}
+
+fn check_dims(dims: &[usize]) -> PlanResult<Dims> {
+    if dims.is_empty() {
+        return Err(Error::InvalidArgument("Plan: empty dimensions"))
+    }
+    let last = dims.len() - 1;
+    let (mut total, mut half) = (1usize, 1usize);
+    for (i, &d) in dims.iter().enumerate() {
+        try!(error::check_int(d));
+        // saturating, so that an overflow reports (at least) the size
+        // that was too large.
+        total = total.saturating_mul(d);
+        half = half.saturating_mul(if i == last { d / 2 + 1 } else { d });
+    }
+    // the complex side of a real transform would have elements even
+    // if the real side has none, so refuse these in every direction.
+    if total == 0 {
+        return Err(Error::InvalidArgument("Plan: zero length"))
+    }
+    try!(error::check_isize(total));
+    try!(error::check_isize(half));
+    Ok(Dims { n: dims.iter().map(|&d| d as c_int).collect(), total: total, half: half })
+}
+
 impl<In: DerefMut<Target = [f64]>, Out: DerefMut<Target = [Complex64]>> Plan<In, Out> {
-    pub fn r2c_1d_prealloc(mut in_: In, mut out: Out) -> Plan<In, Out> {
-        let plan = {
-            let n = in_.len();
-            if out.len() < n / 2 + 1 {
-                panic!("Plan::r2c_prealloc: `out` has length {}, but requires at least {}",
-                      out.len(), n / 2 + 1)
-            }
-            RawPlan::new(|| unsafe {
-                    ffi::fftw_plan_dft_r2c_1d(n as i32,
-                                              in_.as_mut_ptr(),
-                                              out.as_mut_ptr() as *mut ffi::fftw_complex,
-                                              //ffi::FFTW_MEASURE
-                                              ffi::FFTW_ESTIMATE
-                                              )
-                }).unwrap()
-        };
-        Plan { raw: plan, in_: in_, out: out }
+    /// Plan a real-to-complex transform of all of `in_`, into the
+    /// first `in_.len() / 2 + 1` elements of `out`.
+    pub fn r2c_1d_prealloc(in_: In, out: Out) -> PlanResult<Plan<In, Out>> {
+        let n = in_.len();
+        Plan::r2c_nd_prealloc(&[n], &Default::default(), in_, out)
+    }
+
+    /// Plan a real-to-complex transform of a row-major array with
+    /// shape `dims` stored in `in_`, into `out`, which has
+    /// `dims[last] / 2 + 1` elements along the last axis.
+    pub fn r2c_nd_prealloc(dims: &[usize], opts: &PlanOptions,
+                           mut in_: In, mut out: Out) -> PlanResult<Plan<In, Out>> {
+        let dims = try!(check_dims(dims));
+        if let Some(Direction::Backward) = opts.direction {
+            return Err(Error::Unsupported("backward real-to-complex"))
+        }
+        try!(error::check_len(Buffer::Input, dims.total, in_.len()));
+        try!(error::check_len(Buffer::Output, dims.half, out.len()));
+
+        let plan = try!(unsafe {r2c_raw(&dims, opts, in_.as_mut_ptr(), out.as_mut_ptr())});
+        Ok(Plan { raw: plan, in_: in_, out: out })
     }
 }
 impl Plan<FftwVec<f64>, FftwVec<Complex64>> {
-    pub fn r2c_1d(n: usize) -> Plan<FftwVec<f64>, FftwVec<Complex64>> {
-        unsafe {
-            let (in_, out) = (FftwVec::uninit(n), FftwVec::uninit(n / 2 + 1));
-
-            Plan::r2c_1d_prealloc(in_, out)
+    /// Plan a real-to-complex transform of length `n` with the default
+    /// options; see `r2c_nd` for others.
+    pub fn r2c_1d(n: usize) -> PlanResult<Plan<FftwVec<f64>, FftwVec<Complex64>>> {
+        Plan::r2c_nd(&[n], &Default::default())
+    }
+    /// Plan a real-to-complex transform of an `n0` by `n1` row-major
+    /// array; see `r2c_nd`.
+    pub fn r2c_2d(n0: usize, n1: usize,
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<f64>, FftwVec<Complex64>>> {
+        Plan::r2c_nd(&[n0, n1], opts)
+    }
+    /// Plan a real-to-complex transform of an `n0` by `n1` by `n2`
+    /// row-major array; see `r2c_nd`.
+    pub fn r2c_3d(n0: usize, n1: usize, n2: usize,
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<f64>, FftwVec<Complex64>>> {
+        Plan::r2c_nd(&[n0, n1, n2], opts)
+    }
+    /// Plan a real-to-complex transform of a row-major array with
+    /// shape `dims`, allocating the buffers.
+    pub fn r2c_nd(dims: &[usize],
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<f64>, FftwVec<Complex64>>> {
+        let dims_ = try!(check_dims(dims));
+        if let Some(Direction::Backward) = opts.direction {
+            return Err(Error::Unsupported("backward real-to-complex"))
         }
+        let (mut in_, mut out) = (FftwVec::uninit(dims_.total), FftwVec::uninit(dims_.half));
+
+        let plan = try!(unsafe {r2c_raw(&dims_, opts, in_.as_mut_ptr(), out.as_mut_ptr())});
+        // planning may scribble over the arrays, so only fill them in
+        // now.
+        Ok(Plan { raw: plan, in_: in_.zeroed(), out: out.zeroed() })
     }
 }
+unsafe fn r2c_raw(dims: &Dims, opts: &PlanOptions,
+                  in_: *mut f64, out: *mut Complex64) -> PlanResult<RawPlan> {
+    plan_with_flags(opts.flags(), || {
+        ffi::fftw_plan_dft_r2c(dims.n.len() as c_int, dims.n.as_ptr(),
+                               in_,
+                               out as *mut ffi::fftw_complex,
+                               opts.flags())
+    })
+}
+
+/// Check that a complex-to-real transform can be planned.
+fn check_c2r(dims: &Dims, opts: &PlanOptions) -> PlanResult<()> {
+    if let Some(Direction::Forward) = opts.direction {
+        return Err(Error::Unsupported("forward complex-to-real"))
+    }
+    Ok(())
+}
+
 impl<In: DerefMut<Target = [Complex64]>, Out: DerefMut<Target = [f64]>> Plan<In, Out> {
-    pub fn c2r_1d_prealloc(mut in_: In, mut out: Out) -> Plan<In, Out> {
-        let plan = {
-            let n = out.len();
-            if in_.len() > n / 2 + 1 {
-                panic!("Plan::r2c_prealloc: `in_` has length {}, but requires at most {}",
-                      in_.len(), n / 2 + 1)
-            }
-            RawPlan::new(|| unsafe {
-                    ffi::fftw_plan_dft_c2r_1d(n as i32,
-                                              in_.as_mut_ptr() as *mut ffi::fftw_complex,
-                                              out.as_mut_ptr(),
-                                              ffi::FFTW_ESTIMATE)
-                }).unwrap()
-        };
-        Plan { raw: plan, in_: in_, out: out }
+    /// Plan a complex-to-real transform with real length `n`, using
+    /// the first `n / 2 + 1` elements of `in_` and `n` of `out`.
+    pub fn c2r_1d_prealloc(n: usize, in_: In, out: Out) -> PlanResult<Plan<In, Out>> {
+        Plan::c2r_nd_prealloc(&[n], &Default::default(), in_, out)
+    }
+
+    /// Plan a complex-to-real transform from `in_` into a row-major
+    /// array with shape `dims` stored in `out`; `in_` has
+    /// `dims[last] / 2 + 1` elements along the last axis.
+    pub fn c2r_nd_prealloc(dims: &[usize], opts: &PlanOptions,
+                           mut in_: In, mut out: Out) -> PlanResult<Plan<In, Out>> {
+        let dims = try!(check_dims(dims));
+        try!(check_c2r(&dims, opts));
+        try!(error::check_len(Buffer::Input, dims.half, in_.len()));
+        try!(error::check_len(Buffer::Output, dims.total, out.len()));
+
+        let plan = try!(unsafe {c2r_raw(&dims, opts, in_.as_mut_ptr(), out.as_mut_ptr())});
+        Ok(Plan { raw: plan, in_: in_, out: out })
     }
 }
 impl Plan<FftwVec<Complex64>, FftwVec<f64>> {
-    pub fn c2r_1d(n: usize) -> Plan<FftwVec<Complex64>, FftwVec<f64>> {
-        unsafe {
-            let (in_, out) = (FftwVec::uninit(n / 2 + 1), FftwVec::uninit(n));
+    /// Plan a complex-to-real transform with real length `n` with the
+    /// default options; see `c2r_nd` for others.
+    pub fn c2r_1d(n: usize) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<f64>>> {
+        Plan::c2r_nd(&[n], &Default::default())
+    }
+    /// Plan a complex-to-real transform into an `n0` by `n1` row-major
+    /// real array; see `c2r_nd`.
+    pub fn c2r_2d(n0: usize, n1: usize,
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<f64>>> {
+        Plan::c2r_nd(&[n0, n1], opts)
+    }
+    /// Plan a complex-to-real transform into an `n0` by `n1` by `n2`
+    /// row-major real array; see `c2r_nd`.
+    pub fn c2r_3d(n0: usize, n1: usize, n2: usize,
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<f64>>> {
+        Plan::c2r_nd(&[n0, n1, n2], opts)
+    }
+    /// Plan a complex-to-real transform into a row-major real array
+    /// with shape `dims`, allocating the buffers.
+    pub fn c2r_nd(dims: &[usize],
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<f64>>> {
+        let dims_ = try!(check_dims(dims));
+        try!(check_c2r(&dims_, opts));
+        let (mut in_, mut out) = (FftwVec::uninit(dims_.half), FftwVec::uninit(dims_.total));
 
-            Plan::c2r_1d_prealloc(in_, out)
-        }
+        let plan = try!(unsafe {c2r_raw(&dims_, opts, in_.as_mut_ptr(), out.as_mut_ptr())});
+        Ok(Plan { raw: plan, in_: in_.zeroed(), out: out.zeroed() })
     }
 }
+unsafe fn c2r_raw(dims: &Dims, opts: &PlanOptions,
+                  in_: *mut Complex64, out: *mut f64) -> PlanResult<RawPlan> {
+    plan_with_flags(opts.flags(), || {
+        ffi::fftw_plan_dft_c2r(dims.n.len() as c_int, dims.n.as_ptr(),
+                               in_ as *mut ffi::fftw_complex,
+                               out,
+                               opts.flags())
+    })
+}
 
 impl<In: DerefMut<Target = [Complex64]>, Out: DerefMut<Target = [Complex64]>> Plan<In, Out> {
-    pub fn c2c_1d_prealloc(mut in_: In, mut out: Out) -> Plan<In, Out> {
-        let plan = {
-            let n = in_.len();
-            if out.len() < n {
-                panic!("Plan::r2c_prealloc: `out` has length {}, but requires at least {}",
-                      out.len(), n)
-            }
-            RawPlan::new(|| unsafe {
-                    ffi::fftw_plan_dft_1d(n as i32,
-                                          in_.as_mut_ptr() as *mut ffi::fftw_complex,
-                                          out.as_mut_ptr() as *mut ffi::fftw_complex,
-                                          ffi::FFTW_FORWARD,
-                                          ffi::FFTW_ESTIMATE
-                                          //ffi::FFTW_MEASURE
-                                          )
-                }).unwrap()
-        };
-        Plan { raw: plan, in_: in_, out: out }
+    /// Plan a forward complex-to-complex transform of all of `in_`
+    /// into `out`.
+    pub fn c2c_1d_prealloc(in_: In, out: Out) -> PlanResult<Plan<In, Out>> {
+        let n = in_.len();
+        Plan::c2c_nd_prealloc(&[n], &Default::default(), in_, out)
+    }
+
+    /// Plan a complex-to-complex transform of a row-major array with
+    /// shape `dims` stored in `in_`, into `out`.
+    pub fn c2c_nd_prealloc(dims: &[usize], opts: &PlanOptions,
+                           mut in_: In, mut out: Out) -> PlanResult<Plan<In, Out>> {
+        let dims = try!(check_dims(dims));
+        try!(error::check_len(Buffer::Input, dims.total, in_.len()));
+        try!(error::check_len(Buffer::Output, dims.total, out.len()));
+
+        let plan = try!(unsafe {c2c_raw(&dims, opts, in_.as_mut_ptr(), out.as_mut_ptr())});
+        Ok(Plan { raw: plan, in_: in_, out: out })
     }
 }
 impl Plan<FftwVec<Complex64>, FftwVec<Complex64>> {
-    pub fn c2c_1d(n: usize) -> Plan<FftwVec<Complex64>, FftwVec<Complex64>> {
-        unsafe {
-            let (in_, out) = (FftwVec::uninit(n), FftwVec::uninit(n));
+    /// Plan a forward complex-to-complex transform of length `n`; see
+    /// `c2c_nd` for other options.
+    pub fn c2c_1d(n: usize) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<Complex64>>> {
+        Plan::c2c_nd(&[n], &Default::default())
+    }
+    /// Plan a complex-to-complex transform of an `n0` by `n1`
+    /// row-major array; see `c2c_nd`.
+    pub fn c2c_2d(n0: usize, n1: usize,
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<Complex64>>> {
+        Plan::c2c_nd(&[n0, n1], opts)
+    }
+    /// Plan a complex-to-complex transform of an `n0` by `n1` by `n2`
+    /// row-major array; see `c2c_nd`.
+    pub fn c2c_3d(n0: usize, n1: usize, n2: usize,
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<Complex64>>> {
+        Plan::c2c_nd(&[n0, n1, n2], opts)
+    }
+    /// Plan a complex-to-complex transform of a row-major array with
+    /// shape `dims`, allocating the buffers.
+    pub fn c2c_nd(dims: &[usize],
+                  opts: &PlanOptions) -> PlanResult<Plan<FftwVec<Complex64>, FftwVec<Complex64>>> {
+        let dims_ = try!(check_dims(dims));
+        let (mut in_, mut out) = (FftwVec::uninit(dims_.total), FftwVec::uninit(dims_.total));
+
+        let plan = try!(unsafe {c2c_raw(&dims_, opts, in_.as_mut_ptr(), out.as_mut_ptr())});
+        Ok(Plan { raw: plan, in_: in_.zeroed(), out: out.zeroed() })
+    }
+}
+unsafe fn c2c_raw(dims: &Dims, opts: &PlanOptions,
+                  in_: *mut Complex64, out: *mut Complex64) -> PlanResult<RawPlan> {
+    plan_with_flags(opts.flags(), || {
+        ffi::fftw_plan_dft(dims.n.len() as c_int, dims.n.as_ptr(),
+                           in_ as *mut ffi::fftw_complex,
+                           out as *mut ffi::fftw_complex,
+                           opts.sign(),
+                           opts.flags())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ptr, thread};
+    use num::complex::Complex64;
+    use rand::random;
+    use builder2::{Direction, Rigor};
+    use error::Error;
+    use super::{Plan, PlanOptions, RawPlan};
+
+    fn copy<T: Copy>(dst: &mut [T], src: &[T]) {
+        for (a, b) in dst.iter_mut().zip(src.iter()) {
+            *a = *b
+        }
+    }
+
+    #[test]
+    fn c2c_2d_round_trip() {
+        let fwd_opts = PlanOptions { rigor: Rigor::Measure, ..Default::default() };
+        let inv_opts = PlanOptions { direction: Some(Direction::Backward), ..fwd_opts };
+        let mut fwd = Plan::c2c_2d(3, 5, &fwd_opts).unwrap();
+        let mut inv = Plan::c2c_2d(3, 5, &inv_opts).unwrap();
+
+        let data = (0..15).map(|_| Complex64::new(random(), random())).collect::<Vec<_>>();
+        copy(fwd.input(), &data);
+        copy(inv.input(), fwd.execute());
+        for (a, b) in inv.execute().iter().zip(data.iter()) {
+            assert!((a.unscale(15.0) - *b).norm() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn real_3d_round_trip() {
+        let opts = Default::default();
+        let mut fwd = Plan::r2c_3d(2, 3, 5, &opts).unwrap();
+        let mut inv = Plan::c2r_3d(2, 3, 5, &opts).unwrap();
+        assert_eq!(fwd.input().len(), 30);
+        assert_eq!(inv.input().len(), 18);
 
-            Plan::c2c_1d_prealloc(in_, out)
+        let data = (0..30).map(|_| random()).collect::<Vec<f64>>();
+        copy(fwd.input(), &data);
+        copy(inv.input(), fwd.execute());
+        for (a, b) in inv.execute().iter().zip(data.iter()) {
+            assert!((*a / 30.0 - *b).abs() < 1e-10);
         }
     }
+
+    #[test]
+    fn null_plans_are_rejected() {
+        let res = thread::spawn(|| unsafe {
+            RawPlan::new_unchecked(ptr::null_mut());
+        }).join();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn invalid_options() {
+        let backward = PlanOptions { direction: Some(Direction::Backward), ..Default::default() };
+        assert_eq!(Plan::r2c_2d(4, 4, &backward).err(),
+                   Some(Error::Unsupported("backward real-to-complex")));
+        let forward = PlanOptions { direction: Some(Direction::Forward), ..Default::default() };
+        assert_eq!(Plan::c2r_nd(&[4], &forward).err(),
+                   Some(Error::Unsupported("forward complex-to-real")));
+        assert_eq!(Plan::c2c_nd(&[], &forward).err(),
+                   Some(Error::InvalidArgument("Plan: empty dimensions")));
+
+        let zero = Some(Error::InvalidArgument("Plan: zero length"));
+        assert_eq!(Plan::r2c_2d(4, 0, &Default::default()).err(), zero);
+        assert_eq!(Plan::c2r_2d(4, 0, &Default::default()).err(), zero);
+        assert_eq!(Plan::c2c_1d(0).err(), zero);
+        assert_eq!(Plan::r2c_1d_prealloc(vec![], vec![Complex64::new(0.0, 0.0)]).err(), zero);
+    }
 }
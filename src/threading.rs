@@ -0,0 +1,279 @@
+//! Choosing how many threads plans use.
+//!
+//! FFTW's threads help large transforms, but slow small ones down, so
+//! `Threads::Auto` plans each transform with several thread counts
+//! and keeps the cheapest, by the cost FFTW measured when planning.
+//! With `Estimate` rigor nothing is measured, so one thread is used
+//! and nothing is remembered. The decision is remembered for each transform, so later plans of the same
+//! transform are only created once, and can be saved and loaded with
+//! wisdom (`wisdom::WisdomGuard` does this automatically).
+//!
+//! Multi-threaded plans need FFTW's threads library, enabled with the
+//! `threads` or `omp` feature. Without it, every plan is
+//! single-threaded: `Auto` always chooses one thread, and asking for
+//! more with `Fixed` is an error.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::path::Path;
+use std::sync::{Mutex, Once, ONCE_INIT};
+use libc::c_uint;
+
+use ffi;
+use builder2::{Dim, Kind};
+use error::{Error, PlanResult};
+use plan::{self, RawPlan};
+
+/// How many threads a plan uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Threads {
+    /// Always use this many threads (zero is treated as one).
+    Fixed(usize),
+    /// Use whichever number of threads between one and this is
+    /// fastest for the transform.
+    Auto(usize),
+}
+
+impl Default for Threads {
+    fn default() -> Threads {
+        Threads::Fixed(1)
+    }
+}
+
+/// Whether FFTW's threads library is linked, so plans can use more
+/// than one thread.
+pub fn available() -> bool {
+    cfg!(any(feature = "threads", feature = "omp"))
+}
+
+#[cfg(any(feature = "threads", feature = "omp"))]
+unsafe fn set_planner_threads(n: usize) {
+    // cleaning up with `context` tears the threads library down, so
+    // this initialises it again if necessary.
+    ::context::init_threads();
+    ffi::fftw_plan_with_nthreads(n as ::libc::c_int)
+}
+#[cfg(not(any(feature = "threads", feature = "omp")))]
+unsafe fn set_planner_threads(_: usize) {}
+
+/// Plan with `f` and `flags`, with FFTW using `n` threads.
+pub fn plan_with_threads<F>(n: usize, flags: c_uint, f: &F) -> PlanResult<RawPlan>
+    where F: Fn() -> ffi::fftw_plan
+{
+    if n <= 1 {
+        return plan::plan_with_flags(flags, || f())
+    }
+    if !available() {
+        return Err(Error::Unsupported("multi-threaded plans without FFTW's threads library"))
+    }
+    // the number of threads is global state of the planner, so set
+    // it inside the lock, and reset it for everyone else.
+    plan::plan_with_flags(flags, || unsafe {
+        set_planner_threads(n);
+        let p = f();
+        set_planner_threads(1);
+        p
+    })
+}
+
+/// Plan with `f` and `flags` using the best number of threads up to
+/// `max` for the transform described by `key`, as remembered or by
+/// trying powers of two (and `max`).
+///
+/// The estimated cost doesn't depend on the number of threads, so if
+/// FFTW didn't measure the plans, this uses one thread and remembers
+/// nothing.
+pub fn plan_auto<F>(key: String, max: usize, flags: c_uint, f: &F) -> PlanResult<RawPlan>
+    where F: Fn() -> ffi::fftw_plan
+{
+    if let Some(n) = decision(&key) {
+        // decisions loaded from elsewhere may not be possible here,
+        // and may have been made with a larger maximum.
+        let n = if available() { cmp::max(cmp::min(n, max), 1) } else { 1 };
+        return plan_with_threads(n, flags, f)
+    }
+    if max <= 1 || !available() {
+        return plan_with_threads(1, flags, f)
+    }
+
+    let mut best: Option<(usize, f64, RawPlan)> = None;
+    let mut n = 1;
+    loop {
+        let p = try!(plan_with_threads(n, flags, f));
+        let cost = p.cost();
+        if cost <= 0.0 {
+            return Ok(p)
+        }
+        // ties go to fewer threads.
+        if best.as_ref().map_or(true, |b| cost < b.1) {
+            best = Some((n, cost, p));
+        }
+        if n >= max {
+            break
+        }
+        n = if n * 2 > max { max } else { n * 2 };
+    }
+    let (n, _, p) = best.unwrap();
+    debug!("threads for {}: {}", key, n);
+    decisions().lock().unwrap().insert(key, n);
+    Ok(p)
+}
+
+/// A description of a transform, identifying its remembered number
+/// of threads.
+///
+/// Restricting planning to wisdom doesn't change the transform, so
+/// that flag is ignored.
+pub fn descriptor(kind: Kind, dims: &[Dim], howmany: &[Dim], inplace: bool,
+                  flags: c_uint) -> String {
+    let kind = match kind {
+        Kind::C2c => "c2c",
+        Kind::R2c => "r2c",
+        Kind::C2r => "c2r",
+        Kind::R2r => "r2r",
+    };
+    let mut s = format!("f64-{}-{}-{}", kind, if inplace { "inplace" } else { "io" },
+                        flags & !ffi::FFTW_WISDOM_ONLY);
+    for &(sep, ds) in [("/", dims), ("*", howmany)].iter() {
+        s.push_str(sep);
+        for d in ds.iter() {
+            let _ = write!(s, "[{},{},{}]", d.n, d.in_stride, d.out_stride);
+        }
+    }
+    s
+}
+
+static DECISIONS_INIT: Once = ONCE_INIT;
+static mut DECISIONS: *const Mutex<HashMap<String, usize>> =
+    0 as *const Mutex<HashMap<String, usize>>;
+
+fn decisions() -> &'static Mutex<HashMap<String, usize>> {
+    unsafe {
+        DECISIONS_INIT.call_once(|| {
+            let m = Box::new(Mutex::new(HashMap::new()));
+            DECISIONS = mem::transmute::<Box<Mutex<HashMap<String, usize>>>, *const _>(m);
+        });
+        &*DECISIONS
+    }
+}
+
+/// The remembered number of threads for the transform described by
+/// `key`.
+pub fn decision(key: &str) -> Option<usize> {
+    decisions().lock().unwrap().get(key).cloned()
+}
+
+/// Forget every decision.
+pub fn forget() {
+    decisions().lock().unwrap().clear()
+}
+
+/// The decisions as text, one `threads descriptor` per line.
+pub fn export_to_string() -> String {
+    let decisions = decisions().lock().unwrap();
+    let mut lines = decisions.iter().map(|(k, n)| format!("{} {}\n", n, k)).collect::<Vec<_>>();
+    // a stable order, so saved files only change when the decisions
+    // do.
+    lines.sort();
+    lines.concat()
+}
+
+/// Remember the decisions in `s`, as produced by `export_to_string`,
+/// failing (and remembering nothing) if it is malformed.
+pub fn import_from_string(s: &str) -> bool {
+    let mut parsed = vec![];
+    for line in s.lines().filter(|l| !l.trim().is_empty()) {
+        let mut parts = line.trim().splitn(2, ' ');
+        match (parts.next().and_then(|n| n.parse().ok()), parts.next()) {
+            (Some(n), Some(key)) => parsed.push((key.to_string(), n)),
+            _ => return false,
+        }
+    }
+    decisions().lock().unwrap().extend(parsed);
+    true
+}
+
+/// Attempt to save the decisions to `p`.
+pub fn export_to_file(p: &Path) -> bool {
+    use std::io::Write;
+    File::create(p).and_then(|mut f| f.write_all(export_to_string().as_bytes())).is_ok()
+}
+
+/// Attempt to load decisions from `p`.
+pub fn import_from_file(p: &Path) -> bool {
+    let mut s = String::new();
+    let read: io::Result<usize> = File::open(p).and_then(|mut f| f.read_to_string(&mut s));
+    read.is_ok() && import_from_string(&s)
+}
+
+#[cfg(test)]
+mod tests {
+    use num::complex::Complex64;
+    use mem::FftwVec;
+    use builder2::{Dim, Kind, Planner, Rigor};
+    use error::Error;
+    use super::{Threads, available, decision, descriptor, export_to_string, import_from_string};
+
+    #[test]
+    fn auto_remembers() {
+        let plan = Planner::new().rigor(Rigor::Measure).threads(Threads::Auto(4))
+            .input(FftwVec::<Complex64>::zeros(1 << 12))
+            .output(FftwVec::<Complex64>::zeros(1 << 12)).plan();
+        assert!(plan.is_ok());
+
+        let dims = [Dim { n: 1 << 12, in_stride: 1, out_stride: 1 }];
+        let key = descriptor(Kind::C2c, &dims, &[], false, ::ffi::FFTW_MEASURE);
+        let n = decision(&key);
+        if available() {
+            assert!(n.map_or(false, |n| 1 <= n && n <= 4));
+        } else {
+            assert_eq!(n, None);
+        }
+
+        let fixed = Planner::new().threads(Threads::Fixed(2))
+            .input(FftwVec::<Complex64>::zeros(8)).output(FftwVec::<Complex64>::zeros(8)).plan();
+        assert_eq!(fixed.is_ok(), available());
+        if !available() {
+            assert_eq!(fixed.err(),
+                       Some(Error::Unsupported("multi-threaded plans without FFTW's threads \
+                                                library")));
+        }
+    }
+
+    #[test]
+    fn estimates_are_not_remembered() {
+        let plan = Planner::new().threads(Threads::Auto(4))
+            .input(FftwVec::<Complex64>::zeros(1 << 11))
+            .output(FftwVec::<Complex64>::zeros(1 << 11)).plan();
+        assert!(plan.is_ok());
+
+        let dims = [Dim { n: 1 << 11, in_stride: 1, out_stride: 1 }];
+        assert_eq!(decision(&descriptor(Kind::C2c, &dims, &[], false, ::ffi::FFTW_ESTIMATE)),
+                   None);
+    }
+
+    #[test]
+    fn descriptors() {
+        let dims = [Dim { n: 8, in_stride: 1, out_stride: -2 }];
+        let howmany = [Dim { n: 3, in_stride: 8, out_stride: 16 }];
+        assert_eq!(descriptor(Kind::R2c, &dims, &howmany, false, 0),
+                   "f64-r2c-io-0/[8,1,-2]*[3,8,16]");
+        assert_eq!(descriptor(Kind::C2c, &dims, &[], true, ::ffi::FFTW_WISDOM_ONLY),
+                   "f64-c2c-inplace-0/[8,1,-2]*");
+    }
+
+    #[test]
+    fn export_import() {
+        assert!(import_from_string("3 test-key/[5,1,1]*\n\n2 test-other\n"));
+        assert_eq!(decision("test-key/[5,1,1]*"), Some(3));
+        assert_eq!(decision("test-other"), Some(2));
+        assert!(export_to_string().contains("3 test-key/[5,1,1]*\n"));
+
+        assert!(!import_from_string("x test-bad\n"));
+        assert_eq!(decision("test-bad"), None);
+    }
+}
@@ -1,10 +1,13 @@
 use std::path::{Path, PathBuf};
-use {ffi, lock};
+use std::thread;
+use {ffi, lock, threading};
 
 /// Import and export FFTW wisdom implicitly.
 ///
 /// The destructor will save wisdom to the file from which it was
-/// loaded.
+/// loaded. The number of threads chosen for each transform by
+/// `threading::Threads::Auto` is kept alongside, in the same path
+/// with `.threads` appended.
 ///
 /// The `wisdom` macro performs this automatically.
 ///
@@ -27,15 +30,24 @@ impl WisdomGuard {
     /// that does not exist (yet) and it will be created on the next run.
     pub fn import(p: &Path) -> WisdomGuard {
         import_from_file(p);
+        threading::import_from_file(&threads_path(p));
         WisdomGuard { p: p.to_path_buf() }
     }
 }
 impl Drop for WisdomGuard {
     fn drop(&mut self) {
         export_to_file(self.p.as_path());
+        threading::export_to_file(&threads_path(&self.p));
     }
 }
 
+/// Where the threading decisions for the wisdom in `p` are kept.
+fn threads_path(p: &Path) -> PathBuf {
+    let mut s = p.as_os_str().to_os_string();
+    s.push(".threads");
+    PathBuf::from(s)
+}
+
 #[macro_export]
 macro_rules! wisdom {
     ($p: expr) => { let _guard = fftw3::wisdom::WisdomGuard::import($p); }
@@ -44,23 +56,33 @@ macro_rules! wisdom {
 
 /// Attempt to load the system's wisdom.
 pub fn import_from_system() -> bool {
-    unsafe {
-        lock::run(|| ffi::fftw_import_system_wisdom() != 0)
-    }
+    run(|| unsafe {ffi::fftw_import_system_wisdom() != 0})
 }
 
 /// Attempt to save wisdom to `p`.
 pub fn export_to_file(p: &Path) -> bool {
     let v = p.as_os_str().to_cstring().unwrap();
-    unsafe {
-        lock::run(|| ffi::fftw_export_wisdom_to_filename(v.as_ptr() as *const i8) != 0)
-    }
+    run(|| unsafe {ffi::fftw_export_wisdom_to_filename(v.as_ptr()) != 0})
 }
 
 /// Attempt to load wisdom from `p`.
 pub fn import_from_file(p: &Path) -> bool {
     let v = p.as_os_str().to_cstring().unwrap();
-    unsafe {
-        lock::run(|| ffi::fftw_import_wisdom_from_filename(v.as_ptr() as *const i8) != 0)
+    run(|| unsafe {ffi::fftw_import_wisdom_from_filename(v.as_ptr()) != 0})
+}
+
+/// Run the wisdom operation `f` inside the lock.
+///
+/// Importing and exporting wisdom runs the planner hooks, so a panic
+/// in one is raised here, unless this thread is already panicking
+/// (e.g. a `WisdomGuard` being dropped while unwinding), in which
+/// case it is discarded.
+fn run<F: FnOnce() -> bool>(f: F) -> bool {
+    let ok = lock::run(f);
+    if thread::panicking() {
+        lock::take_hook_panic();
+    } else {
+        lock::check_hook_panic();
     }
+    ok
 }
@@ -21,7 +21,7 @@ fn almost_eq_c(x: &[Complex<f64>], y: &[Complex<f64>]) -> bool {
 const N: usize = 32;
 
 macro_rules! smoke_test {
-    ($forward: ident, $inverse: ident, $ctor: expr,
+    ($forward: ident, $inverse: ident [$($arg: expr),*], $ctor: expr,
      $n: expr,
      $scale: expr,
      $cmp: expr) => {{
@@ -37,7 +37,7 @@ macro_rules! smoke_test {
             let mut inv = Planner::new()
                 .direction(Direction::Backward)
                 .rigor(rigor)
-                .$inverse(in_, out)
+                .$inverse($($arg,)* in_, out)
                 .plan().ok().unwrap();
 
             for x in plan.input().iter_mut() {
@@ -59,12 +59,12 @@ macro_rules! smoke_test {
 
 #[test]
 fn c2c_smoke_test() {
-    smoke_test!(c2c, c2c, Complex::new(random(), random()), N,
+    smoke_test!(c2c, c2c [], Complex::new(random(), random()), N,
                 |x: &Complex<_>, scale| x.scale(scale), almost_eq_c)
 }
 #[test]
 fn r2c_c2r_smoke_test() {
-    smoke_test!(r2c, c2r, random(), N / 2 + 1, |x: &f64, scale| *x * scale, almost_eq)
+    smoke_test!(r2c, c2r [N], random(), N / 2 + 1, |x: &f64, scale| *x * scale, almost_eq)
 }
 
 #[test]
@@ -96,3 +96,71 @@ fn c2c_inplace_smoke_test() {
         assert!(almost_eq_c(inv.input(), &*data));
     }
 }
+
+#[test]
+fn invalid_buffers_are_errors() {
+    use fftw3::{Error, Buffer};
+
+    let res = Planner::new()
+        .r2c(fftw3::FftwVec::zeros(N), fftw3::FftwVec::zeros(N / 2))
+        .plan();
+    assert_eq!(res.err(),
+               Some(Error::BufferTooSmall { buffer: Buffer::Output, required: N / 2 + 1,
+                                            actual: N / 2 }));
+
+    let res = Planner::new()
+        .direction(Direction::Backward)
+        .c2r(N, fftw3::FftwVec::zeros(0), fftw3::FftwVec::zeros(N))
+        .plan();
+    assert_eq!(res.err(),
+               Some(Error::BufferTooSmall { buffer: Buffer::Input, required: N / 2 + 1,
+                                            actual: 0 }));
+
+    let res = Planner::new()
+        .c2r(N + 1, fftw3::FftwVec::zeros(N / 2 + 1), fftw3::FftwVec::zeros(N))
+        .plan();
+    assert_eq!(res.err(),
+               Some(Error::BufferTooSmall { buffer: Buffer::Output, required: N + 1,
+                                            actual: N }));
+
+    let res = Planner::new()
+        .c2r(0, fftw3::FftwVec::zeros(1), fftw3::FftwVec::zeros(1))
+        .plan();
+    assert!(res.is_err());
+
+    let res = Planner::new()
+        .direction(Direction::Backward)
+        .r2c(fftw3::FftwVec::zeros(N), fftw3::FftwVec::zeros(N))
+        .plan();
+    assert!(match res { Err(Error::Unsupported(_)) => true, _ => false });
+}
+
+#[test]
+fn r2c_c2r_odd_and_even_lengths() {
+    for n in 1..18 {
+        let data = (0..n).map(|_| random()).collect::<Vec<f64>>();
+
+        // the old builder
+        let mut fwd = Planner::new()
+            .r2c(fftw3::FftwVec::zeros(n), fftw3::FftwVec::zeros(n / 2 + 1))
+            .plan().unwrap();
+        let mut inv = Planner::new()
+            .c2r(n, fftw3::FftwVec::zeros(n / 2 + 1), fftw3::FftwVec::zeros(n))
+            .plan().unwrap();
+        fwd.input().clone_from_slice(&data);
+        fwd.execute();
+        inv.input().clone_from_slice(fwd.output().unwrap());
+        inv.execute();
+        let round_trip = inv.output().unwrap().iter().map(|x| *x / n as f64).collect::<Vec<_>>();
+        assert!(almost_eq(&round_trip, &data), "{}: {:?} {:?}", n, round_trip, data);
+
+        // the simple plans
+        let mut fwd = fftw3::Plan::r2c_1d(n).unwrap();
+        let mut inv = fftw3::Plan::c2r_1d_prealloc(n, fftw3::FftwVec::zeros(n / 2 + 1),
+                                                   fftw3::FftwVec::zeros(n)).unwrap();
+        fwd.input().clone_from_slice(&data);
+        inv.input().clone_from_slice(fwd.execute());
+        let round_trip = inv.execute().iter().map(|x| *x / n as f64).collect::<Vec<_>>();
+        assert!(almost_eq(&round_trip, &data), "{}: {:?} {:?}", n, round_trip, data);
+    }
+}